@@ -0,0 +1,26 @@
+
+struct Accumulation {
+    @location(0) accum: vec4f,
+    @location(1) revealage: f32
+}
+
+// Weighted blended order-independent transparency (McGuire and Bavoil 2013).
+// Every fragment is added into the accumulation target, weighted so closer
+// surfaces dominate, while the revealage target is multiplied by how much of
+// the background each lets through. Both blend operations commute, so the
+// draw order doesn't matter. See `composite.wgsl` for the resolve.
+@fragment
+fn frag(in: VertexOutput) -> Accumulation {
+    let render = ctx.render;
+    let value = render.ambiant + render.intensity * (1.0 - pow(facing(in), render.edge_falloff));
+    let color = base_color(in);
+    let alpha = clamp(color.a * value, 0.0, 1.0);
+
+    let depth = 1.0 - in.pos.z * 0.9;
+    let weight = clamp(pow(min(1.0, alpha * 10.0) + 0.01, 3.0) * 1e8 * pow(depth, 3.0), 1e-2, 3e3);
+
+    return Accumulation(
+        vec4(color.rgb * alpha, alpha) * weight,
+        alpha
+    );
+}
@@ -1,39 +1,91 @@
-use std::mem;
+use std::{iter, mem, path::PathBuf};
 
+use anyhow::Error;
 use compute::{
-    bindings::{IndexBuffer, UniformBuffer, VertexBuffer},
+    bindings::{StorageBuffer, UniformBuffer},
     export::{
-        egui::{Context, Key, Slider, Window},
-        nalgebra::{Matrix4, Vector3},
+        egui::{Align2, Area, Button, Color32, ComboBox, Context, Frame, Key, Slider, Window},
+        nalgebra::{Matrix4, Vector2, Vector3, Vector4},
         wgpu::RenderPass,
     },
+    gpu::Gpu,
     interactive::{GraphicsCtx, Interactive},
     pipeline::render::RenderPipeline,
 };
 use encase::ShaderType;
+use itertools::Itertools;
 
 use crate::{
     camera::Camera,
-    simulation::Simulation,
-    ui::{dragger, sci_dragger, sci_dragger::SciDragValue, vec3_dragger},
+    colormap::{self, ColorMap},
+    marching_cubes::{gpu::GpuMarchingCubes, Mesher, Normals},
+    mesh::{Mesh, MAX_ENTRIES},
+    runner::{Runner, Shared},
+    simulation::{
+        boundary::{BoundaryCondition, FACES},
+        gpu::{FieldCopy, Solver},
+        medium::Medium,
+        pml::Pml,
+        scalar::Scalar,
+        shape::Shape,
+        Source,
+    },
+    slice::Slices,
+    transparency::{RenderMode, Transparency},
+    ui::{
+        color_bar, color_picker, dragger, sci_dragger, sci_dragger::SciDragValue,
+        shape::shape_editor, vec3_dragger,
+    },
     vertex::Vertex,
+    volume::Volume,
 };
 
 pub struct App {
+    /// Draws surfaces in the opaque render mode.
     pub render: RenderPipeline,
-    pub index: IndexBuffer,
-    pub vertex: VertexBuffer<Vertex>,
+    pub transparency: Transparency,
+    pub mesh: Mesh,
+    pub obstacle_mesh: Mesh,
     pub uniform: UniformBuffer<Uniform>,
-    pub indicies: u32,
 
-    pub simulation: Simulation,
+    pub runner: Runner,
+    /// Grid size applied by the resize button.
+    pub grid_size: Vector3<usize>,
+    /// Interpolate the field into the new grid when resizing, instead of
+    /// starting over at rest.
+    pub resample: bool,
     pub camera: Camera,
-    pub use_iso_level: bool,
-    pub iso_level: f32,
+    pub iso_levels: Vec<IsoLevel>,
+    /// Also mesh every iso-level negated, showing the troughs of the wave.
+    pub two_sided: bool,
+    pub field: StorageBuffer<Vec<f32>>,
+    /// Fills the field from the GPU solver while it's in use, created the
+    /// first time it's needed.
+    pub field_copy: Option<FieldCopy>,
+    pub volume: Volume,
+    pub volume_rendering: bool,
+    pub slices: Slices,
+    pub render_mode: RenderMode,
+    pub mesher: Mesher,
+    pub gpu_mesher: GpuMarchingCubes,
+    /// Triangulate the iso-surfaces on the GPU, only supported for marching
+    /// cubes. Vertex scalars aren't computed by it, so colouring by a scalar
+    /// still meshes on the CPU.
+    pub gpu_meshing: bool,
+    pub scalar: Scalar,
+    pub color_map: ColorMap,
+    /// Range of the vertex scalars in the current mesh.
+    pub scalar_range: Vector2<f32>,
+    pub normals: Normals,
     pub render_config: RenderConfig,
 
     pub scheduled_remesh: bool,
+    pub scheduled_obstacle_remesh: bool,
     pub energy: bool,
+    pub media_error: Option<Error>,
+    /// How many sources were left outside the grid and removed by the last
+    /// resize.
+    pub dropped_sources: usize,
 }
 
 #[derive(ShaderType, Clone, Copy)]
@@ -41,6 +93,23 @@ pub struct RenderConfig {
     pub ambiant: f32,
     pub intensity: f32,
     pub edge_falloff: f32,
+
+    /// Colour and opacity of obstacle surfaces.
+    pub obstacle: Vector4<f32>,
+}
+
+/// The most iso-levels that can be shown at once, limited by the size of the
+/// colour array in `render.wgsl`, which has room for both sides of each.
+pub const MAX_ISO_LEVELS: usize = 8;
+
+#[derive(Clone)]
+pub struct IsoLevel {
+    pub enabled: bool,
+    pub value: f32,
+    /// Colour and opacity of the surface at `value`.
+    pub positive: Vector4<f32>,
+    /// Colour and opacity of the surface at `-value`, if two sided.
+    pub negative: Vector4<f32>,
 }
 
 #[derive(ShaderType, Default)]
@@ -48,51 +117,426 @@ pub struct Uniform {
     view_projection: Matrix4<f32>,
     camera_dir: Vector3<f32>,
     render: RenderConfig,
+    /// Colour of each surface, indexed by vertex level.
+    surfaces: [Vector4<f32>; MAX_ISO_LEVELS * 2],
+
+    color_map: [Vector3<f32>; colormap::STOPS],
+    /// Scalar values mapped to the ends of the colour map.
+    scalar_range: Vector2<f32>,
+    color_by_scalar: u32,
 }
 
 impl Interactive for App {
     fn init(&mut self, _gcx: GraphicsCtx) {
-        self.camera.position = self.simulation.config.size.map(|x| x as f32) / 2.0;
+        let size = self.runner.handle().lock().simulation.config.size;
+        self.camera.position = size.map(|x| x as f32) / 2.0;
     }
 
-    fn ui(&mut self, _gcx: GraphicsCtx, ctx: &Context) {
+    fn ui(&mut self, gcx: GraphicsCtx, ctx: &Context) {
         self.camera.update(ctx);
+        let handle = self.runner.handle();
+        let mut shared = handle.lock();
+        let shared = &mut *shared;
 
         Window::new("Wave Simulator 3D")
             .default_width(0.0)
             .show(ctx, |ui| {
                 ui.heading("Simulation");
-                sci_dragger(ui, "dx (m)", &mut self.simulation.config.dx);
-                sci_dragger(ui, "dt (s)", &mut self.simulation.config.dt);
-                sci_dragger(ui, "Wave Speed (m/s)", &mut self.simulation.config.v);
+                sci_dragger(ui, "dx (m)", &mut shared.simulation.config.dx);
+                sci_dragger(ui, "dt (s)", &mut shared.simulation.config.dt);
+                sci_dragger(ui, "Wave Speed (m/s)", &mut shared.simulation.config.v);
+                dragger(
+                    ui,
+                    "Damping (1/s)",
+                    &mut shared.simulation.config.damping,
+                    |x| x.speed(0.01).range(0.0..=f32::MAX),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Grid Size");
+                    vec3_dragger(ui, &mut self.grid_size, |x| x.range(2..=usize::MAX));
+                });
+                ui.horizontal(|ui| {
+                    let resized = self.grid_size != shared.simulation.config.size;
+                    if ui.add_enabled(resized, Button::new("Resize")).clicked() {
+                        self.resize(gcx.gpu, shared);
+                    }
+                    ui.checkbox(&mut self.resample, "Resample Field");
+                });
+
+                let mut solver = shared.solver;
+                ComboBox::from_label("Solver")
+                    .selected_text(solver.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in Solver::ALL {
+                            ui.selectable_value(&mut solver, option, option.to_string());
+                        }
+                    });
+                if solver != shared.solver {
+                    shared.set_solver(gcx.gpu, solver);
+                }
+
+                let valid = shared.validate();
+                if let Err(err) = &valid {
+                    ui.colored_label(Color32::RED, err.to_string());
+                } else {
+                    let courant = shared.simulation.config.courant();
+                    ui.label(format!("Courant Number: {courant:.3}"));
+                }
                 let prev_energy = self.energy;
                 ui.checkbox(&mut self.energy, "Wave Energy");
                 self.scheduled_remesh |= prev_energy != self.energy;
 
+                ui.collapsing("Boundaries", |ui| {
+                    let boundaries = &mut shared.simulation.config.boundaries;
+                    for (boundary, name) in boundaries.iter_mut().zip(FACES) {
+                        ComboBox::from_label(name)
+                            .selected_text(boundary.to_string())
+                            .show_ui(ui, |ui| {
+                                for condition in BoundaryCondition::ALL {
+                                    ui.selectable_value(boundary, condition, condition.to_string());
+                                }
+                            });
+                    }
+
+                    ui.separator();
+                    let pml = &mut shared.simulation.config.pml;
+                    let mut enabled = pml.is_some();
+                    ui.checkbox(&mut enabled, "Perfectly Matched Layer");
+                    if enabled != pml.is_some() {
+                        *pml = enabled.then(Pml::default);
+                    }
+
+                    if let Some(pml) = pml {
+                        dragger(ui, "Thickness (cells)", &mut pml.thickness, |x| {
+                            x.range(1..=usize::MAX)
+                        });
+                        sci_dragger(ui, "Reflection", &mut pml.reflection);
+                    }
+                });
+
+                ui.collapsing("Medium", |ui| {
+                    let size = shared.simulation.config.size;
+                    let media = &mut shared.simulation.config.media;
+                    let mut remove = None;
+                    for (i, medium) in media.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            shape_editor(ui, &mut medium.shape, size);
+                            sci_dragger(ui, "Refractive Index", &mut medium.index);
+                            dragger(ui, "Damping (1/s)", &mut medium.damping, |x| {
+                                x.speed(0.01).range(0.0..=f32::MAX)
+                            });
+                            ui.button("Remove").clicked().then(|| remove = Some(i));
+                            ui.separator();
+                        });
+                    }
+
+                    if let Some(i) = remove {
+                        media.remove(i);
+                    }
+
+                    if ui.button("Add Medium").clicked() {
+                        media.push(Medium {
+                            shape: Shape::default_sphere(size),
+                            index: 1.5,
+                            damping: 0.0,
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        let file = &mut shared.simulation.config.media_file;
+                        let mut text = (file.as_ref())
+                            .map(|x| x.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        ui.text_edit_singleline(&mut text);
+                        ui.label("Index File");
+                        *file = (!text.is_empty()).then(|| PathBuf::from(text));
+                    });
+
+                    if ui.button("Apply").clicked() {
+                        self.media_error = shared.simulation.rebuild_media().err();
+                        shared.upload();
+                    }
+
+                    if let Some(err) = &self.media_error {
+                        ui.colored_label(Color32::RED, err.to_string());
+                    }
+                });
+
+                ui.collapsing("Obstacles", |ui| {
+                    let size = shared.simulation.config.size;
+                    let obstacles = &mut shared.simulation.config.obstacles;
+                    let prev_obstacles = obstacles.clone();
+                    let mut remove = None;
+                    for (i, obstacle) in obstacles.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            shape_editor(ui, obstacle, size);
+                            ui.button("Remove").clicked().then(|| remove = Some(i));
+                            ui.separator();
+                        });
+                    }
+
+                    if let Some(i) = remove {
+                        obstacles.remove(i);
+                    }
+
+                    if ui.button("Add Obstacle").clicked() {
+                        obstacles.push(Shape::default_slits(size));
+                    }
+
+                    if prev_obstacles != *obstacles {
+                        shared.simulation.rebuild_obstacles();
+                        shared.upload();
+                        self.scheduled_obstacle_remesh = true;
+                    }
+                });
+
+                ui.collapsing("Sources", |ui| {
+                    let sources = &mut shared.simulation.config.sources;
+                    let mut remove = None;
+                    for (i, source) in sources.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Position");
+                                vec3_dragger(ui, &mut source.position, |x| x.speed(0.1));
+                            });
+                            sci_dragger(ui, "Amplitude", &mut source.amplitude);
+                            dragger(ui, "Frequency (rad/tick)", &mut source.frequency, |x| {
+                                x.speed(0.001)
+                            });
+                            dragger(ui, "Phase (rad)", &mut source.phase, |x| x.speed(0.01));
+                            dragger(ui, "Width (cells)", &mut source.width, |x| {
+                                x.speed(0.01).range(0.01..=f32::MAX)
+                            });
+                            ui.button("Remove").clicked().then(|| remove = Some(i));
+                            ui.separator();
+                        });
+                    }
+
+                    if let Some(i) = remove {
+                        sources.remove(i);
+                    }
+                    if ui.button("Add Source").clicked() {
+                        let center = shared.simulation.config.size.map(|x| x as f32) / 2.0;
+                        shared.simulation.config.sources.push(Source::new(center));
+                    }
+
+                    if self.dropped_sources > 0 {
+                        let warning = format!(
+                            "Removed {} sources left outside the grid by resizing",
+                            self.dropped_sources
+                        );
+                        ui.colored_label(Color32::YELLOW, warning);
+                    }
+                });
+
                 ui.add_space(8.0);
                 ui.horizontal(|ui| {
                     let t_down = ui.input(|input| input.key_down(Key::T));
 
                     let remesh = ui.button("Remesh").clicked();
-                    let tick = ui.button("Tick").clicked() || t_down;
+                    let can_tick = valid.is_ok() && !self.runner.running;
+                    let tick = ui.add_enabled(can_tick, Button::new("Tick")).clicked()
+                        || (t_down && can_tick);
                     let reset = ui.button("Reset").clicked();
 
-                    reset.then(|| self.simulation.reset());
-                    tick.then(|| self.simulation.tick());
+                    let label = if self.runner.running { "Pause" } else { "Play" };
+                    if ui.add_enabled(valid.is_ok(), Button::new(label)).clicked() {
+                        self.runner.running ^= true;
+                    }
+
+                    reset.then(|| shared.reset());
+                    tick.then(|| shared.tick());
                     self.scheduled_remesh |= tick || remesh || reset;
                 });
 
+                let config = &shared.simulation.config;
+                let time = shared.simulation.step as f32 * config.dt;
+                ui.label(format!("Time: {time:.3} s ({} steps)", shared.simulation.step));
+                dragger(ui, "Steps per Frame", &mut self.runner.steps_per_frame, |x| {
+                    x.range(1..=usize::MAX)
+                });
+                dragger(ui, "Remesh Every (steps)", &mut self.runner.remesh_every, |x| {
+                    x.range(1..=usize::MAX)
+                });
+
+                let target = &mut self.runner.target_time;
+                let mut enabled = target.is_some();
+                ui.checkbox(&mut enabled, "Stop at Target Time");
+                if enabled != target.is_some() {
+                    *target = enabled.then_some(time + 100.0 * config.dt);
+                }
+                if let Some(target) = target {
+                    sci_dragger(ui, "Target Time (s)", target);
+                }
+
                 ui.add_space(8.0);
                 ui.heading("Rendering");
-                let (prev_use_iso_level, prev_iso_level) = (self.use_iso_level, self.iso_level);
-                ui.horizontal(|ui| {
-                    ui.checkbox(&mut self.use_iso_level, "");
-                    SciDragValue::new(&mut self.iso_level).show(ui);
-                    ui.label("Iso Level");
+                // Only the levels themselves need a remesh, not their colours
+                let meshed = |app: &Self| {
+                    let levels = app.iso_levels.iter().map(|x| (x.enabled, x.value));
+                    (app.two_sided, levels.collect::<Vec<_>>())
+                };
+                let prev_meshed = meshed(self);
+                let prev_volume = self.volume_rendering;
+                ui.checkbox(&mut self.volume_rendering, "Volume Rendering");
+                self.scheduled_remesh |= prev_volume != self.volume_rendering;
+
+                if self.volume_rendering {
+                    ui.collapsing("Transfer Function", |ui| {
+                        let transfer = &mut self.volume.transfer;
+                        ui.horizontal(|ui| {
+                            color_picker(ui, &mut transfer.positive);
+                            color_picker(ui, &mut transfer.negative);
+                            ui.label("Colour (+/-)");
+                        });
+                        sci_dragger(ui, "Cutoff", &mut transfer.cutoff);
+                        sci_dragger(ui, "Saturation", &mut transfer.saturation);
+                        dragger(ui, "Step Size (cells)", &mut self.volume.step_size, |x| {
+                            x.speed(0.01).range(0.05..=f32::MAX)
+                        });
+                    });
+                }
+
+                ui.collapsing("Iso Levels", |ui| {
+                    let two_sided = self.two_sided && !self.energy;
+                    let mut remove = None;
+                    for (i, level) in self.iso_levels.iter_mut().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut level.enabled, "");
+                                SciDragValue::new(&mut level.value).show(ui);
+                                color_picker(ui, &mut level.positive);
+                                if two_sided {
+                                    color_picker(ui, &mut level.negative);
+                                }
+                                ui.button("Remove").clicked().then(|| remove = Some(i));
+                            });
+                        });
+                    }
+
+                    if let Some(i) = remove {
+                        self.iso_levels.remove(i);
+                    }
+
+                    let add = Button::new("Add Iso Level");
+                    if ui
+                        .add_enabled(self.iso_levels.len() < MAX_ISO_LEVELS, add)
+                        .clicked()
+                    {
+                        let value = self.iso_levels.last().map_or(0.4, |x| x.value / 2.0);
+                        self.iso_levels.push(IsoLevel::new(value));
+                    }
+
+                    ui.add_enabled_ui(!self.energy, |ui| {
+                        ui.checkbox(&mut self.two_sided, "Two Sided")
+                    });
+                });
+                self.scheduled_remesh |= prev_meshed != meshed(self);
+
+                ui.collapsing("Slices", |ui| {
+                    let size = shared.simulation.config.size;
+                    let prev_enabled = self.slices.enabled();
+                    for (axis, name) in ["X", "Y", "Z"].into_iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let plane = &mut self.slices.planes[axis];
+                            let mut enabled = plane.is_some();
+                            ui.checkbox(&mut enabled, name);
+                            if enabled != plane.is_some() {
+                                *plane = enabled.then_some(size[axis] as f32 / 2.0);
+                            }
+
+                            if let Some(plane) = plane {
+                                ui.add(Slider::new(plane, 0.0..=(size[axis] - 1) as f32));
+                            }
+                        });
+                    }
+
+                    sci_dragger(ui, "Range", &mut self.slices.range);
+                    ui.horizontal(|ui| {
+                        ui.add(Slider::new(&mut self.slices.opacity, 0.0..=1.0));
+                        ui.label("Opacity");
+                    });
+                    let color_map = &mut self.slices.color_map;
+                    ComboBox::new("slice_color_map", "Colour Map")
+                        .selected_text(color_map.to_string())
+                        .show_ui(ui, |ui| {
+                            for map in ColorMap::ALL {
+                                ui.selectable_value(color_map, map, map.to_string());
+                            }
+                        });
+                    self.scheduled_remesh |= prev_enabled != self.slices.enabled();
                 });
-                self.scheduled_remesh |=
-                    prev_use_iso_level != self.use_iso_level || prev_iso_level != self.iso_level;
+
+                let prev_scalar = self.scalar;
+                ComboBox::from_label("Colour By")
+                    .selected_text(self.scalar.to_string())
+                    .show_ui(ui, |ui| {
+                        for scalar in Scalar::ALL {
+                            ui.selectable_value(&mut self.scalar, scalar, scalar.to_string());
+                        }
+                    });
+                self.scheduled_remesh |= prev_scalar != self.scalar;
+
+                if self.scalar != Scalar::None {
+                    ComboBox::from_label("Colour Map")
+                        .selected_text(self.color_map.to_string())
+                        .show_ui(ui, |ui| {
+                            for map in ColorMap::ALL {
+                                ui.selectable_value(&mut self.color_map, map, map.to_string());
+                            }
+                        });
+                }
+
+                ComboBox::from_label("Render Mode")
+                    .selected_text(self.render_mode.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in RenderMode::ALL {
+                            ui.selectable_value(&mut self.render_mode, mode, mode.to_string());
+                        }
+                    });
+
+                let prev_mesher = self.mesher;
+                ComboBox::from_label("Mesher")
+                    .selected_text(self.mesher.to_string())
+                    .show_ui(ui, |ui| {
+                        for mesher in Mesher::ALL {
+                            ui.selectable_value(&mut self.mesher, mesher, mesher.to_string());
+                        }
+                    });
+                if prev_mesher != self.mesher {
+                    self.scheduled_remesh = true;
+                    self.scheduled_obstacle_remesh = true;
+                }
+
+                if self.mesher == Mesher::Cubes {
+                    let checkbox = ui.checkbox(&mut self.gpu_meshing, "GPU Triangulation");
+                    self.scheduled_remesh |= checkbox.changed();
+                }
+
+                let meshes = [("Iso-surface", &self.mesh), ("Obstacle", &self.obstacle_mesh)];
+                for (name, mesh) in meshes {
+                    if let Some(needed) = mesh.truncated {
+                        let warning = format!(
+                            "{name} mesh truncated, it needs {needed} entries but only {MAX_ENTRIES} fit"
+                        );
+                        ui.colored_label(Color32::YELLOW, warning);
+                    }
+                }
+
+                let mut gradient_normals = self.normals == Normals::Gradient;
+                if ui
+                    .checkbox(&mut gradient_normals, "Gradient Normals")
+                    .changed()
+                {
+                    self.normals = [Normals::Face, Normals::Gradient][gradient_normals as usize];
+                    self.scheduled_remesh = true;
+                    self.scheduled_obstacle_remesh = true;
+                }
 
+                ui.horizontal(|ui| {
+                    color_picker(ui, &mut self.render_config.obstacle);
+                    ui.label("Obstacles");
+                });
                 ui.horizontal(|ui| {
                     ui.add(Slider::new(&mut self.render_config.ambiant, 0.0..=1.0));
                     ui.label("Ambiant");
@@ -120,36 +564,189 @@ impl Interactive for App {
                     dragger(ui, "Far", &mut self.camera.far, |x| x.speed(0.1));
                 });
             });
+
+        self.scheduled_remesh |= self.runner.update(shared);
+
+        if self.scalar != Scalar::None && !self.volume_rendering {
+            Area::new("Colour Bar".into())
+                .anchor(Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+                .show(ctx, |ui| {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(self.scalar.to_string());
+                        color_bar(ui, self.color_map, self.scalar_range);
+                    });
+                });
+        }
     }
 
     fn render(&mut self, gcx: GraphicsCtx, render_pass: &mut RenderPass) {
-        if mem::take(&mut self.scheduled_remesh) {
-            let iso_level = self.use_iso_level.then_some(self.iso_level);
-            let iso_level = iso_level.unwrap_or_default();
+        let handle = self.runner.handle();
+        let mut shared = handle.lock();
+        let remesh = mem::take(&mut self.scheduled_remesh);
+        if remesh {
+            self.runner.meshed_step = shared.simulation.step;
+        }
 
-            let (vertices, indices) = if self.energy {
-                self.simulation.triangluate_energy(iso_level)
+        // The volume, slices and GPU mesher all read the field straight from
+        // the GPU, so it's copied over there from the GPU solver if in use
+        let gpu_meshing =
+            self.gpu_meshing && self.mesher == Mesher::Cubes && self.scalar == Scalar::None;
+        if remesh && (self.volume_rendering || self.slices.enabled() || gpu_meshing) {
+            if let Some(gpu_solver) = shared.active_gpu_solver() {
+                let field_copy = self.field_copy.get_or_insert_with(|| {
+                    FieldCopy::new(gcx.gpu, gpu_solver, &self.field).unwrap()
+                });
+                field_copy.copy(&shared.simulation, self.energy).unwrap();
+            } else if self.energy {
+                self.field.upload(&shared.simulation.energy).unwrap();
+            } else {
+                self.field.upload(shared.simulation.state()).unwrap();
+            }
+        }
+
+        if remesh && !self.volume_rendering && gpu_meshing {
+            let iso_levels = self.surfaces().map(|(x, _)| x).collect::<Vec<_>>();
+            let mut entries = self.gpu_mesher.mesh(&iso_levels, self.normals).unwrap();
+            if self.mesh.reserve(gcx.gpu, entries as u64).unwrap() {
+                let size = shared.simulation.config.size;
+                self.gpu_mesher =
+                    GpuMarchingCubes::new(gcx.gpu, &self.field, size, &self.mesh).unwrap();
+                entries = self.gpu_mesher.mesh(&iso_levels, self.normals).unwrap();
+            }
+            self.mesh.written(entries as u64);
+        } else if remesh && !self.volume_rendering {
+            shared.sync();
+            let iso_levels = self.surfaces().map(|(x, _)| x).collect::<Vec<_>>();
+            let (mut vertices, indices) = if self.energy {
+                shared
+                    .simulation
+                    .triangluate_energy(self.mesher, &iso_levels, self.normals)
             } else {
-                self.simulation.triangluate(iso_level)
+                shared
+                    .simulation
+                    .triangluate(self.mesher, &iso_levels, self.normals)
             };
 
-            self.indicies = indices.len() as u32;
-            self.vertex.upload(&vertices).unwrap();
-            self.index.upload(&indices).unwrap();
+            if self.scalar != Scalar::None {
+                shared.simulation.vertex_scalars(self.scalar, &mut vertices);
+                let range = (vertices.iter().map(|x| x.scalar))
+                    .filter(|x| x.is_finite())
+                    .minmax()
+                    .into_option();
+                self.scalar_range =
+                    range.map_or(Vector2::new(0.0, 1.0), |(min, max)| Vector2::new(min, max));
+            }
+
+            if (self.mesh).upload(gcx.gpu, &vertices, &indices).unwrap() {
+                let size = shared.simulation.config.size;
+                self.gpu_mesher =
+                    GpuMarchingCubes::new(gcx.gpu, &self.field, size, &self.mesh).unwrap();
+            }
+        }
+
+        if mem::take(&mut self.scheduled_obstacle_remesh) {
+            let (mut vertices, indices) = shared
+                .simulation
+                .triangluate_obstacles(self.mesher, self.normals);
+            vertices.iter_mut().for_each(|x| x.level = Vertex::OBSTACLE);
+            (self.obstacle_mesh)
+                .upload(gcx.gpu, &vertices, &indices)
+                .unwrap();
+        }
+        drop(shared);
+
+        let window = gcx.window.inner_size();
+        let aspect = window.width as f32 / window.height as f32;
+        let mut surfaces = [Vector4::zeros(); MAX_ISO_LEVELS * 2];
+        for (surface, (_, color)) in surfaces.iter_mut().zip(self.surfaces()) {
+            *surface = color;
         }
 
-        let window = gcx.window.inner_size().cast::<f32>();
-        let aspect = window.width / window.height;
         self.uniform
             .upload(&Uniform {
                 view_projection: self.camera.view_projection(aspect),
                 camera_dir: self.camera.facing(),
                 render: self.render_config,
+                surfaces,
+
+                color_map: self.color_map.stops(),
+                scalar_range: self.scalar_range,
+                color_by_scalar: (self.scalar != Scalar::None) as u32,
             })
             .unwrap();
 
-        self.render
-            .draw(render_pass, &self.index, &self.vertex, 0..self.indicies);
+        let mut meshes = vec![&self.obstacle_mesh];
+        if !self.volume_rendering {
+            meshes.push(&self.mesh);
+        }
+
+        match self.render_mode {
+            RenderMode::Opaque => {
+                for mesh in meshes {
+                    (self.render).draw(render_pass, &mesh.index, &mesh.vertex, 0..mesh.count);
+                }
+            }
+            RenderMode::Transparent => {
+                let size = Vector2::new(window.width, window.height);
+                (self.transparency).render(gcx.gpu, render_pass, size, &meshes);
+            }
+        }
+
+        // The slices and volume blend over whatever is behind them, so they
+        // go after the opaque geometry, with the volume last.
+        if self.slices.enabled() {
+            self.slices.render(render_pass, &self.camera, aspect);
+        }
+
+        if self.volume_rendering {
+            self.volume.render(render_pass, &self.camera, aspect);
+        }
+    }
+}
+
+impl App {
+    /// Resizes the simulation to the chosen grid size and recreates
+    /// everything on the GPU that depends on it. Errors rebuilding the media
+    /// are shown with the rest of the media settings.
+    fn resize(&mut self, gpu: &Gpu, shared: &mut Shared) {
+        shared.sync();
+        let sources = shared.simulation.config.sources.len();
+        self.media_error = (shared.simulation)
+            .resize(self.grid_size, self.resample)
+            .err();
+        self.dropped_sources = sources - shared.simulation.config.sources.len();
+
+        // The size is left as it was if it was rejected
+        let size = shared.simulation.config.size;
+        self.grid_size = size;
+
+        self.field = gpu.create_storage(shared.simulation.state()).unwrap();
+        self.field_copy = None;
+        let mut volume = Volume::new(gpu, &self.field, size).unwrap();
+        (volume.transfer, volume.step_size) = (self.volume.transfer, self.volume.step_size);
+        self.volume = volume;
+        let mut slices = Slices::new(gpu, &self.field, size).unwrap();
+        (slices.planes, slices.range) = (self.slices.planes, self.slices.range);
+        (slices.opacity, slices.color_map) = (self.slices.opacity, self.slices.color_map);
+        self.slices = slices;
+
+        shared.recreate_gpu_solver(gpu);
+        self.gpu_mesher = GpuMarchingCubes::new(gpu, &self.field, size, &self.mesh).unwrap();
+        self.scheduled_remesh = true;
+        self.scheduled_obstacle_remesh = true;
+    }
+
+    /// The iso-level and colour of every surface to draw, in the same order
+    /// as the vertex levels they are meshed into.
+    fn surfaces(&self) -> impl Iterator<Item = (f32, Vector4<f32>)> + '_ {
+        // Energy is never negative, so there is no second surface to show
+        let two_sided = self.two_sided && !self.energy;
+        (self.iso_levels.iter())
+            .filter(|x| x.enabled)
+            .flat_map(move |level| {
+                let negative = two_sided.then_some((-level.value, level.negative));
+                iter::once((level.value, level.positive)).chain(negative)
+            })
     }
 }
 
@@ -159,6 +756,19 @@ impl Default for RenderConfig {
             ambiant: 0.1,
             intensity: 0.9,
             edge_falloff: 0.1,
+
+            obstacle: Vector4::repeat(1.0),
+        }
+    }
+}
+
+impl IsoLevel {
+    pub fn new(value: f32) -> Self {
+        Self {
+            enabled: true,
+            value,
+            positive: Vector4::repeat(1.0),
+            negative: Vector4::new(0.3, 0.5, 1.0, 1.0),
         }
     }
 }
@@ -0,0 +1,6 @@
+// See `ColorMap::sample`
+fn color_map(t: f32) -> vec3f {
+    let x = clamp(t, 0.0, 1.0) * 8.0;
+    let low = min(u32(x), 7u);
+    return mix(ctx.color_map[low], ctx.color_map[low + 1u], x - f32(low));
+}
@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+use compute::export::nalgebra::Vector3;
+
+/// Number of evenly spaced colours each map is defined by, which the shaders
+/// linearly interpolate between.
+pub const STOPS: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorMap {
+    Viridis,
+    Inferno,
+    /// Diverging map, best for values centered on zero.
+    Coolwarm,
+}
+
+impl ColorMap {
+    pub const ALL: [Self; 3] = [Self::Viridis, Self::Inferno, Self::Coolwarm];
+
+    pub fn stops(self) -> [Vector3<f32>; STOPS] {
+        match self {
+            Self::Viridis => [
+                Vector3::new(0.267, 0.005, 0.329),
+                Vector3::new(0.283, 0.141, 0.458),
+                Vector3::new(0.254, 0.265, 0.530),
+                Vector3::new(0.192, 0.407, 0.556),
+                Vector3::new(0.149, 0.511, 0.558),
+                Vector3::new(0.122, 0.619, 0.537),
+                Vector3::new(0.208, 0.719, 0.473),
+                Vector3::new(0.431, 0.808, 0.346),
+                Vector3::new(0.993, 0.906, 0.144),
+            ],
+            Self::Inferno => [
+                Vector3::new(0.001, 0.000, 0.014),
+                Vector3::new(0.106, 0.047, 0.255),
+                Vector3::new(0.290, 0.047, 0.420),
+                Vector3::new(0.471, 0.110, 0.427),
+                Vector3::new(0.647, 0.173, 0.376),
+                Vector3::new(0.812, 0.267, 0.275),
+                Vector3::new(0.929, 0.412, 0.145),
+                Vector3::new(0.984, 0.608, 0.024),
+                Vector3::new(0.988, 1.000, 0.643),
+            ],
+            Self::Coolwarm => [
+                Vector3::new(0.230, 0.299, 0.754),
+                Vector3::new(0.348, 0.465, 0.888),
+                Vector3::new(0.484, 0.622, 0.975),
+                Vector3::new(0.667, 0.779, 0.993),
+                Vector3::new(0.865, 0.865, 0.865),
+                Vector3::new(0.958, 0.769, 0.678),
+                Vector3::new(0.958, 0.603, 0.482),
+                Vector3::new(0.873, 0.392, 0.309),
+                Vector3::new(0.706, 0.016, 0.150),
+            ],
+        }
+    }
+
+    /// Colour at `t`, which is clamped to the range zero to one.
+    pub fn sample(self, t: f32) -> Vector3<f32> {
+        let stops = self.stops();
+        let t = t.clamp(0.0, 1.0) * (STOPS - 1) as f32;
+        let low = (t as usize).min(STOPS - 2);
+        stops[low].lerp(&stops[low + 1], t - low as f32)
+    }
+}
+
+impl Display for ColorMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Viridis => "Viridis",
+            Self::Inferno => "Inferno",
+            Self::Coolwarm => "Coolwarm",
+        })
+    }
+}
@@ -0,0 +1,29 @@
+struct VertexInput {
+    @location(0) pos: vec4f,
+    @location(1) normal: vec3f,
+    @location(2) level: u32
+}
+
+@group(0) @binding(0) var accum: texture_2d<f32>;
+@group(0) @binding(1) var revealage: texture_2d<f32>;
+
+@vertex
+fn vert(in: VertexInput) -> @builtin(position) vec4f {
+    return in.pos;
+}
+
+// Resolves the transparent surfaces accumulated by `accumulate.wgsl` onto
+// the frame, as their weighted average colour covering one minus the
+// revealage.
+@fragment
+fn frag(@builtin(position) pos: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(pos.xy);
+    let revealage = textureLoad(revealage, pixel, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+
+    let accum = textureLoad(accum, pixel, 0);
+    let color = accum.rgb / max(accum.a, 1e-5);
+    return vec4(color, 1.0 - revealage);
+}
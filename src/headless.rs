@@ -0,0 +1,475 @@
+//! Runs the simulation without a window, writing snapshots and metrics to disk.
+//!
+//! ```plain
+//! wave-sim-3d headless [--size 100] [--dx 0.1] [--dt 0.01] [--v 1.0]
+//!                      [--steps 1000] [--snapshot-every 100] [--out output] [--energy]
+//!                      [--source x,y,z[,amplitude,frequency,phase,width]]...
+//!                      [--boundary [face=]condition]... [--pml thickness[,reflection]]
+//!                      [--damping 0.0] [--media-file path] [--medium shape=index[,damping]]...
+//!                      [--obstacle shape]... [--solver cpu|gpu] [--software]
+//! wave-sim-3d verify [headless options] [--tolerance 1e-4]
+//! ```
+//!
+//! Passing any `--source` replaces the default pair of sources. Boundaries
+//! apply to every face unless prefixed with one of `-x`, `+x`, `-y`, `+y`,
+//! `-z` or `+z`. Shapes are written as `kind:params`, see the `FromStr`
+//! implementation of [`Shape`](crate::simulation::shape::Shape) for the
+//! supported kinds.
+//!
+//! The GPU solver runs on the fallback (software) adapter if `--software` is
+//! passed. `verify` ticks the CPU and GPU solvers side by side, by default on
+//! a 32³ grid for 200 steps, and fails if any cell of their states or
+//! energies differ by more than the tolerance relative to the largest value.
+//! It then checks GPU marching cubes against its CPU reference on the final
+//! state, and the reference against the CPU mesher.
+//!
+//! Snapshots are the raw little-endian `f32` cells of the current state, laid
+//! out the same way as [`Simulation::states`] (z fastest, then y, then x).
+//! With `--energy`, each snapshot also writes the time averaged `u²` of every
+//! cell from [`Simulation::energy`] alongside it as `energy_{step}.bin`.
+//!
+//! `metrics.csv` has the step, time, largest absolute amplitude and the sum of
+//! `u²` over every cell, which tracks the field's strength but isn't the
+//! physical energy since it leaves out `u_t` and the gradient.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use compute::{export::nalgebra::Vector3, gpu::Gpu};
+
+use crate::{
+    marching_cubes::{
+        gpu::{self, GpuMarchingCubes},
+        marching_cubes, Normals,
+    },
+    mesh::Mesh,
+    simulation::{
+        boundary::{Boundaries, FACES},
+        gpu::{GpuSolver, Solver},
+        medium::Medium,
+        pml::Pml,
+        Config, Simulation, Source,
+    },
+    vertex::Vertex,
+};
+
+struct Args {
+    config: Config,
+    steps: usize,
+    snapshot_every: usize,
+    energy: bool,
+    out: PathBuf,
+    solver: Solver,
+    software: bool,
+    tolerance: f32,
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let args = Args::parse(args)?;
+    fs::create_dir_all(&args.out)
+        .with_context(|| format!("Failed to create `{}`", args.out.display()))?;
+
+    let mut simulation = Simulation::new(args.config)?;
+    simulation.validate()?;
+    args.solver.validate(&simulation.config)?;
+
+    let gpu = match args.solver {
+        Solver::Cpu => None,
+        Solver::Gpu => Some(gpu(args.software)?),
+    };
+    let mut gpu_solver = (gpu.as_ref())
+        .map(|gpu| GpuSolver::new(gpu, &simulation))
+        .transpose()?;
+
+    let mut metrics = BufWriter::new(File::create(args.out.join("metrics.csv"))?);
+    writeln!(metrics, "step,time,max_amplitude,sum_squared_amplitude")?;
+
+    let start = Instant::now();
+    for _ in 0..args.steps {
+        match &mut gpu_solver {
+            Some(solver) => {
+                solver.tick(&mut simulation)?;
+                solver.download(&mut simulation)?;
+            }
+            None => simulation.tick(),
+        }
+
+        let step = simulation.step;
+        let state = simulation.state();
+        let max_amplitude = state.iter().fold(0.0_f32, |acc, x| acc.max(x.abs()));
+        let sum_squared_amplitude = state.iter().map(|x| x * x).sum::<f32>();
+        let time = step as f32 * simulation.config.dt;
+        writeln!(
+            metrics,
+            "{step},{time},{max_amplitude},{sum_squared_amplitude}"
+        )?;
+
+        if args.snapshot_every != 0 && step.is_multiple_of(args.snapshot_every) {
+            write_field(&args.out.join(format!("state_{step:06}.bin")), state)?;
+            if args.energy {
+                let path = args.out.join(format!("energy_{step:06}.bin"));
+                write_field(&path, &simulation.energy)?;
+            }
+        }
+    }
+
+    metrics.flush()?;
+    println!(
+        "Ran {} steps on a {}×{}×{} grid in {:.2?}",
+        args.steps,
+        simulation.config.size.x,
+        simulation.config.size.y,
+        simulation.config.size.z,
+        start.elapsed()
+    );
+
+    Ok(())
+}
+
+pub fn verify(args: impl Iterator<Item = String>) -> Result<()> {
+    let defaults = ["--size", "32", "--steps", "200"].map(String::from);
+    let args = Args::parse(defaults.into_iter().chain(args))?;
+
+    let mut cpu = Simulation::new(args.config.clone())?;
+    cpu.validate()?;
+    Solver::Gpu.validate(&cpu.config)?;
+
+    let gpu = gpu(args.software)?;
+    let mut simulation = Simulation::new(args.config)?;
+    let mut solver = GpuSolver::new(&gpu, &simulation)?;
+
+    let size = simulation.config.size;
+    let mut worst = 0.0_f32;
+    for _ in 0..args.steps {
+        cpu.tick();
+        solver.tick(&mut simulation)?;
+        solver.download(&mut simulation)?;
+
+        let fields = [
+            ("state", cpu.state(), simulation.state()),
+            ("energy", &cpu.energy, &simulation.energy),
+        ];
+        for (name, expected, actual) in fields {
+            let (error, idx) = relative_error(expected, actual);
+            worst = worst.max(error);
+            ensure!(
+                error <= args.tolerance,
+                "GPU {name} differs from the CPU by {error:.2e} on step {} at cell ({}, {}, {}), \
+                 expected {} but found {}",
+                simulation.step,
+                idx / (size.y * size.z),
+                idx / size.z % size.y,
+                idx % size.z,
+                expected[idx],
+                actual[idx]
+            );
+        }
+    }
+
+    println!(
+        "GPU solver matches the CPU for {} steps on a {}×{}×{} grid, with a worst relative error of {worst:.2e}",
+        args.steps, size.x, size.y, size.z
+    );
+
+    let iso_levels = [0.1, -0.1];
+    let field = gpu.create_storage(cpu.state())?;
+    for (normals, name) in [(Normals::Face, "face"), (Normals::Gradient, "gradient")] {
+        let expected = gpu::reference(cpu.state(), size, &iso_levels, normals);
+        let mesh = Mesh::with_capacity(&gpu, expected.len().max(1) as u64)?;
+        let mut mesher = GpuMarchingCubes::new(&gpu, &field, size, &mesh)?;
+
+        let count = mesher.mesh(&iso_levels, normals)? as usize;
+        ensure!(
+            count == expected.len(),
+            "GPU marching cubes with {name} normals made {count} vertices, expected {}",
+            expected.len()
+        );
+
+        let error = mesh_error(expected, mesh.vertex.download()?, size.max() as f32);
+        ensure!(
+            error <= args.tolerance,
+            "GPU marching cubes with {name} normals differs from the CPU by {error:.2e}"
+        );
+        println!(
+            "GPU marching cubes with {name} normals matches the CPU for {} triangles, with a worst relative error of {error:.2e}",
+            count / 3
+        );
+    }
+
+    // The CPU mesher welds vertices on the same grid point, dropping the
+    // triangles the reference has with two corners there. Face normals are
+    // averaged between triangles, so only gradient normals can be compared.
+    let (vertices, indices) = marching_cubes(cpu.state(), size, &iso_levels, Normals::Gradient);
+    let actual = (indices.iter()).map(|&x| vertices[x as usize]).collect();
+    let expected = gpu::reference(cpu.state(), size, &iso_levels, Normals::Gradient);
+    let expected = (expected.chunks_exact(3))
+        .filter(|x| (0..3).all(|i| x[i].position != x[(i + 1) % 3].position))
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    ensure!(
+        indices.len() == expected.len(),
+        "The GPU marching cubes reference made {} vertices, but the CPU mesher made {}",
+        expected.len(),
+        indices.len()
+    );
+
+    let error = mesh_error(expected, actual, size.max() as f32);
+    ensure!(
+        error <= args.tolerance,
+        "The GPU marching cubes reference differs from the CPU mesher by {error:.2e}"
+    );
+    println!(
+        "The GPU marching cubes reference matches the CPU mesher for {} triangles, with a worst relative error of {error:.2e}",
+        indices.len() / 3
+    );
+
+    Ok(())
+}
+
+/// The largest difference between the vertices of two triangle soups with
+/// their cubes in any order, with positions relative to `scale`.
+fn mesh_error(expected: Vec<Vertex>, actual: Vec<Vertex>, scale: f32) -> f32 {
+    let sorted = |vertices: Vec<Vertex>| {
+        let mut triangles = (vertices.chunks_exact(3))
+            .map(|x| [x[0], x[1], x[2]])
+            .collect::<Vec<_>>();
+        // Each cube's triangles are written together in table order, so a
+        // stable sort by the cube they're in lines them up with the reference.
+        triangles.sort_by_key(|triangle| {
+            let center = triangle
+                .iter()
+                .map(|x| x.position.xyz())
+                .sum::<Vector3<f32>>()
+                / 3.0;
+            let cube: [i64; 3] = center.map(|x| x.floor() as i64).into();
+            (triangle[0].level, cube)
+        });
+        triangles.into_iter().flatten()
+    };
+
+    // A NaN anywhere in either mesh is a failure, which `f32::max` would
+    // otherwise quietly skip over.
+    (sorted(expected).zip(sorted(actual)))
+        .map(|(a, b)| {
+            let position = (a.position - b.position).abs().max() / scale;
+            let error = position.max((a.normal - b.normal).abs().max());
+            let finite = (a.position.iter().chain(&b.position))
+                .chain(a.normal.iter().chain(&b.normal))
+                .all(|x| x.is_finite());
+            if finite {
+                error
+            } else {
+                f32::INFINITY
+            }
+        })
+        .fold(0.0, f32::max)
+}
+
+/// The largest difference between two fields relative to the largest
+/// magnitude in `expected`, along with the index of the cell it's at. The
+/// first cell where the difference isn't finite is reported as infinitely
+/// wrong.
+fn relative_error(expected: &[f32], actual: &[f32]) -> (f32, usize) {
+    let scale = (expected.iter())
+        .fold(0.0_f32, |acc, x| acc.max(x.abs()))
+        .max(f32::EPSILON);
+
+    let (mut idx, mut error) = (0, 0.0);
+    for (i, (a, b)) in expected.iter().zip(actual).enumerate() {
+        let difference = (a - b).abs();
+        if !difference.is_finite() {
+            return (f32::INFINITY, i);
+        }
+
+        if difference > error {
+            (idx, error) = (i, difference);
+        }
+    }
+
+    (error / scale, idx)
+}
+
+fn gpu(software: bool) -> Result<Gpu> {
+    Gpu::builder().force_fallback_adapter(software).build()
+}
+
+fn write_field(path: &Path, field: &[f32]) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for value in field {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let Config {
+            mut size,
+            mut v,
+            mut dx,
+            mut dt,
+            mut damping,
+            mut boundaries,
+            mut pml,
+            mut media,
+            mut media_file,
+            mut obstacles,
+            ..
+        } = Config::default();
+        let mut sources = Vec::new();
+        let (mut steps, mut snapshot_every, mut energy) = (1000, 100, false);
+        let mut out = PathBuf::from("output");
+        let (mut solver, mut software, mut tolerance) = (Solver::Cpu, false, 1e-4);
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Expected a value after `{arg}`"))
+            };
+
+            match arg.as_str() {
+                "--size" => size = parse_size(&value()?)?,
+                "--dx" => dx = parse(&arg, &value()?)?,
+                "--dt" => dt = parse(&arg, &value()?)?,
+                "--v" => v = parse(&arg, &value()?)?,
+                "--damping" => damping = parse(&arg, &value()?)?,
+                "--steps" => steps = parse(&arg, &value()?)?,
+                "--snapshot-every" => snapshot_every = parse(&arg, &value()?)?,
+                "--energy" => energy = true,
+                "--out" => out = PathBuf::from(value()?),
+                "--source" => sources.push(parse_source(&value()?)?),
+                "--boundary" => parse_boundary(&value()?, &mut boundaries)?,
+                "--pml" => pml = Some(parse_pml(&value()?)?),
+                "--medium" => media.push(parse_medium(&value()?)?),
+                "--media-file" => media_file = Some(PathBuf::from(value()?)),
+                "--obstacle" => obstacles.push(value()?.parse()?),
+                "--solver" => solver = value()?.parse()?,
+                "--software" => software = true,
+                "--tolerance" => tolerance = parse(&arg, &value()?)?,
+                _ => bail!("Unknown argument `{arg}`"),
+            }
+        }
+
+        let mut config = Config::new(size, v, dx, dt)?;
+        config.damping = damping;
+        config.boundaries = boundaries;
+        config.pml = pml;
+        config.media = media;
+        config.media_file = media_file;
+        config.obstacles = obstacles;
+        if !sources.is_empty() {
+            config.sources = sources;
+        }
+
+        Ok(Args {
+            config,
+            steps,
+            snapshot_every,
+            energy,
+            out,
+            solver,
+            software,
+            tolerance,
+        })
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("Invalid value `{value}` for `{arg}`"))
+}
+
+/// Accepts either a single edge length (`100`) or `XxYxZ` (`100x50x50`).
+fn parse_size(value: &str) -> Result<Vector3<usize>> {
+    let parts = value
+        .split('x')
+        .map(|x| parse::<usize>("--size", x))
+        .collect::<Result<Vec<_>>>()?;
+
+    match parts[..] {
+        [n] => Ok(Vector3::repeat(n)),
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => bail!("Invalid value `{value}` for `--size`"),
+    }
+}
+
+/// Parses `x,y,z` followed by up to four optional source parameters.
+fn parse_source(value: &str) -> Result<Source> {
+    let parts = value
+        .split(',')
+        .map(|x| parse::<f32>("--source", x))
+        .collect::<Result<Vec<_>>>()?;
+
+    if !(3..=7).contains(&parts.len()) {
+        bail!("Invalid value `{value}` for `--source`");
+    }
+
+    let mut source = Source::new(Vector3::new(parts[0], parts[1], parts[2]));
+    let params = [
+        &mut source.amplitude,
+        &mut source.frequency,
+        &mut source.phase,
+        &mut source.width,
+    ];
+    for (param, value) in params.into_iter().zip(&parts[3..]) {
+        *param = *value;
+    }
+
+    Ok(source)
+}
+
+fn parse_boundary(value: &str, boundaries: &mut Boundaries) -> Result<()> {
+    match value.split_once('=') {
+        Some((face, condition)) => {
+            let face = FACES
+                .iter()
+                .position(|x| x.eq_ignore_ascii_case(face))
+                .with_context(|| format!("Unknown face `{face}` for `--boundary`"))?;
+            boundaries[face] = condition.parse()?;
+        }
+        None => *boundaries = [value.parse()?; 6],
+    }
+
+    Ok(())
+}
+
+fn parse_pml(value: &str) -> Result<Pml> {
+    let mut pml = Pml::default();
+    let (thickness, reflection) = match value.split_once(',') {
+        Some((thickness, reflection)) => (thickness, Some(reflection)),
+        None => (value, None),
+    };
+
+    pml.thickness = parse("--pml", thickness)?;
+    if let Some(reflection) = reflection {
+        pml.reflection = parse("--pml", reflection)?;
+    }
+
+    Ok(pml)
+}
+
+fn parse_medium(value: &str) -> Result<Medium> {
+    let (shape, material) = value
+        .rsplit_once('=')
+        .with_context(|| format!("Expected `shape=index` for `--medium`, found `{value}`"))?;
+    let (index, damping) = match material.split_once(',') {
+        Some((index, damping)) => (index, parse("--medium", damping)?),
+        None => (material, 0.0),
+    };
+
+    Ok(Medium {
+        shape: shape.parse()?,
+        index: parse("--medium", index)?,
+        damping,
+    })
+}
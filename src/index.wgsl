@@ -0,0 +1,5 @@
+// Index of a grid point in a field laid out like on the CPU, with the grid
+// size taken from `ctx.size`
+fn index(pos: vec3u) -> u32 {
+    return pos.x * ctx.size.y * ctx.size.z + pos.y * ctx.size.z + pos.z;
+}
@@ -1,25 +1,51 @@
-use anyhow::Result;
+use std::env;
+
+use anyhow::{bail, Result};
 use compute::{
     export::{
-        wgpu::{include_wgsl, CompareFunction, Limits, ShaderStages},
+        nalgebra::Vector2,
+        wgpu::{CompareFunction, Limits, ShaderStages},
         winit::window::WindowAttributes,
     },
     gpu::Gpu,
 };
 
-use app::{App, RenderConfig, Uniform};
+use app::{App, IsoLevel, RenderConfig, Uniform};
 use camera::Camera;
-use marching_cubes::marching_cubes;
-use simulation::{Config, Simulation};
+use colormap::ColorMap;
+use marching_cubes::{gpu::GpuMarchingCubes, Mesher, Normals};
+use mesh::Mesh;
+use runner::Runner;
+use simulation::{scalar::Scalar, Config, Simulation};
+use slice::Slices;
+use transparency::{surface_shader, RenderMode, Transparency};
 use vertex::VERTEX_BUFFER_LAYOUT;
+use volume::Volume;
 mod app;
 mod camera;
+mod colormap;
+mod headless;
 mod marching_cubes;
+mod mesh;
+mod runner;
+mod shader;
 mod simulation;
+mod slice;
+mod transparency;
 mod ui;
 mod vertex;
+mod volume;
 
 fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    if let Some(command) = args.next() {
+        return match command.as_str() {
+            "headless" => headless::run(args),
+            "verify" => headless::verify(args),
+            _ => bail!("Unknown subcommand `{command}`, expected `headless` or `verify`"),
+        };
+    }
+
     let gpu = Gpu::builder()
         .with_limits(Limits {
             max_buffer_size: 2147483647,
@@ -27,42 +53,58 @@ fn main() -> Result<()> {
         })
         .build()?;
 
-    let config = Config::default();
-    let cells = config.size.iter().product();
-    let simulation = Simulation {
-        states: vec![vec![0.0; cells]; 3],
-        energy: vec![0.0; cells],
-        step: 0,
-        config,
-    };
+    let simulation = Simulation::new(Config::default())?;
 
-    let index = gpu.create_index_empty(1_000_000);
-    let vertex = gpu.create_vertex_empty(1_000_000)?;
+    let mesh = Mesh::new(&gpu)?;
+    let obstacle_mesh = Mesh::new(&gpu)?;
     let uniforms = gpu.create_uniform(&Uniform::default())?;
+    let field = gpu.create_storage(simulation.state())?;
+    let volume = Volume::new(&gpu, &field, simulation.config.size)?;
+    let slices = Slices::new(&gpu, &field, simulation.config.size)?;
+    let gpu_mesher = GpuMarchingCubes::new(&gpu, &field, simulation.config.size, &mesh)?;
     let render = gpu
-        .render_pipeline(include_wgsl!("render.wgsl"))
+        .render_pipeline(surface_shader(include_str!("opaque.wgsl")))
         .vertex_layout(VERTEX_BUFFER_LAYOUT)
-        .depth_compare(CompareFunction::Always)
+        .depth_compare(CompareFunction::Less)
         .bind(&uniforms, ShaderStages::VERTEX_FRAGMENT)
         .finish();
+    let transparency = Transparency::new(&gpu, &uniforms)?;
 
     gpu.create_window(
         WindowAttributes::default().with_title("Wave Simulator 3D"),
         App {
             render,
-            index,
-            vertex,
+            transparency,
+            mesh,
+            obstacle_mesh,
             uniform: uniforms,
 
-            indicies: 0,
-            simulation,
+            grid_size: simulation.config.size,
+            resample: true,
+            runner: Runner::new(simulation),
             camera: Camera::default(),
-            iso_level: 0.4,
+            iso_levels: vec![IsoLevel::new(0.4)],
+            two_sided: false,
+            field,
+            field_copy: None,
+            volume,
+            volume_rendering: false,
+            slices,
+            render_mode: RenderMode::Transparent,
+            mesher: Mesher::Cubes,
+            gpu_mesher,
+            gpu_meshing: false,
+            scalar: Scalar::None,
+            color_map: ColorMap::Viridis,
+            scalar_range: Vector2::new(0.0, 1.0),
+            normals: Normals::Face,
             render_config: RenderConfig::default(),
 
             scheduled_remesh: false,
-            use_iso_level: true,
+            scheduled_obstacle_remesh: true,
             energy: false,
+            media_error: None,
+            dropped_sources: 0,
         },
     )
     .run()?;
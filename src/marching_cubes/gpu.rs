@@ -0,0 +1,195 @@
+//! Marching cubes in a compute shader, writing straight into the vertex and
+//! index buffers that are drawn. Every cube reserves space for its triangles
+//! with an atomic counter, so unlike [`marching_cubes`](super::marching_cubes)
+//! no vertices are shared and the triangles come out in no particular order.
+
+use anyhow::{ensure, Result};
+use compute::{
+    bindings::{StorageBuffer, UniformBuffer},
+    export::nalgebra::{Vector3, Vector4},
+    gpu::Gpu,
+    pipeline::compute::ComputePipeline,
+};
+use encase::ShaderType;
+
+use super::{
+    gradient,
+    table::{EDGE_TABLE, TRIANGULATION_TABLE},
+    vertex_interp, Normals, EDGE_CONNECTIONS, GRID_POINTS,
+};
+use crate::{
+    mesh::Mesh,
+    shader::{self, INDEX},
+    vertex::Vertex,
+};
+
+/// Terminates each cube's entry in the flattened triangulation table.
+const END: u32 = u32::MAX;
+
+/// The most iso-levels meshed at once, limited by the size of the level
+/// array in `marching_cubes.wgsl`.
+pub const MAX_LEVELS: usize = 16;
+
+pub struct GpuMarchingCubes {
+    pipeline: ComputePipeline,
+    uniform: UniformBuffer<MeshUniform>,
+    count: StorageBuffer<u32>,
+    /// Only kept alive for the pipeline's bindings.
+    _tables: [StorageBuffer<Vec<u32>>; 2],
+    size: Vector3<usize>,
+    capacity: u32,
+}
+
+#[derive(ShaderType, Default)]
+struct MeshUniform {
+    size: Vector3<u32>,
+    level_count: u32,
+    /// Packed four to an element, as uniform arrays are 16 byte aligned.
+    iso_levels: [Vector4<f32>; MAX_LEVELS / 4],
+    gradient_normals: u32,
+    capacity: u32,
+}
+
+impl GpuMarchingCubes {
+    /// Creates a mesher reading from `field` and writing into the buffers of
+    /// `mesh`. It needs to be recreated whenever they are reallocated.
+    pub fn new(
+        gpu: &Gpu,
+        field: &StorageBuffer<Vec<f32>>,
+        size: Vector3<usize>,
+        mesh: &Mesh,
+    ) -> Result<Self> {
+        let uniform = gpu.create_uniform(&MeshUniform::default())?;
+        let count = gpu.create_storage(&0)?;
+        let edge_table = gpu.create_storage(&EDGE_TABLE.map(u32::from).to_vec())?;
+        let triangulation_table = gpu.create_storage(&triangulation_table())?;
+
+        let pipeline = gpu
+            .compute_pipeline(shader::compose(
+                "marching_cubes.wgsl",
+                &[include_str!("marching_cubes.wgsl"), INDEX],
+            ))
+            .bind(&uniform)
+            .bind(field)
+            .bind(&edge_table)
+            .bind(&triangulation_table)
+            .bind(&mesh.vertex)
+            .bind(&mesh.index)
+            .bind(&count)
+            .finish();
+
+        Ok(Self {
+            pipeline,
+            uniform,
+            count,
+            _tables: [edge_table, triangulation_table],
+            size,
+            capacity: mesh.capacity() as u32,
+        })
+    }
+
+    /// Meshes every iso-level of the field in a single dispatch, setting the
+    /// level of each vertex to the index of its surface like
+    /// [`Mesher::mesh_levels`](super::Mesher::mesh_levels). Returns the
+    /// number of vertices needed, which is also the number of indices. If
+    /// this exceeds the capacity, only the triangles that fit are written,
+    /// see [`Mesh::written`].
+    pub fn mesh(&mut self, iso_levels: &[f32], normals: Normals) -> Result<u32> {
+        ensure!(
+            iso_levels.len() <= MAX_LEVELS,
+            "The GPU mesher supports at most {MAX_LEVELS} iso-levels"
+        );
+
+        let mut packed = [Vector4::zeros(); MAX_LEVELS / 4];
+        for (i, &iso_level) in iso_levels.iter().enumerate() {
+            packed[i / 4][i % 4] = iso_level;
+        }
+
+        // Uploads land before the next dispatch is run, as every dispatch is
+        // submitted straight away
+        self.count.upload(&0)?;
+        self.uniform.upload(&MeshUniform {
+            size: self.size.map(|x| x as u32),
+            level_count: iso_levels.len() as u32,
+            iso_levels: packed,
+            gradient_normals: (normals == Normals::Gradient) as u32,
+            capacity: self.capacity,
+        })?;
+
+        let cubes = self.size.map(|x| x.saturating_sub(1));
+        self.pipeline.dispatch(cubes.map(|x| x.div_ceil(4) as u32));
+        self.count.download()
+    }
+}
+
+/// The same triangles as the compute shader, in cube order, for testing it.
+pub fn reference(
+    scalar_field: &[f32],
+    size: Vector3<usize>,
+    iso_levels: &[f32],
+    normals: Normals,
+) -> Vec<Vertex> {
+    let stride = Vector3::new(size.y * size.z, size.z, 1);
+    let mut vertices = Vec::new();
+
+    for (level, &iso_level) in iso_levels.iter().enumerate() {
+        for x in 0..size.x.saturating_sub(1) {
+            for y in 0..size.y - 1 {
+                for z in 0..size.z - 1 {
+                    let pos = Vector3::new(x, y, z);
+                    let mut cube_index = 0;
+                    for (i, offset) in GRID_POINTS.iter().enumerate() {
+                        let inside = scalar_field[(pos + offset).dot(&stride)] < iso_level;
+                        cube_index |= (inside as usize) << i;
+                    }
+
+                    // Position and interpolated gradient of the vertex on an edge
+                    let edge = |edge: u8| {
+                        let (a, b) = EDGE_CONNECTIONS[edge as usize];
+                        let (a, b) = (pos + GRID_POINTS[a], pos + GRID_POINTS[b]);
+                        let (val_1, val_2) =
+                            (scalar_field[a.dot(&stride)], scalar_field[b.dot(&stride)]);
+                        [
+                            (a.map(|x| x as f32), b.map(|x| x as f32)),
+                            (
+                                gradient(scalar_field, size, a),
+                                gradient(scalar_field, size, b),
+                            ),
+                        ]
+                        .map(|(a, b)| vertex_interp(iso_level, (a, val_1), (b, val_2)))
+                    };
+
+                    for triangle in TRIANGULATION_TABLE[cube_index].chunks(3) {
+                        let [a, b, c] = [0, 1, 2].map(|i| edge(triangle[i]));
+                        let face = (b[0] - a[0]).cross(&(c[0] - a[0]));
+                        vertices.extend([a, b, c].map(|[point, gradient]| {
+                            let normal = match normals {
+                                Normals::Face => face,
+                                Normals::Gradient => gradient,
+                            };
+                            Vertex {
+                                level: level as u32,
+                                ..Vertex::new(
+                                    point.push(1.0),
+                                    normal.try_normalize(0.0).unwrap_or_default(),
+                                )
+                            }
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    vertices
+}
+
+/// [`TRIANGULATION_TABLE`] padded out to sixteen entries per cube.
+fn triangulation_table() -> Vec<u32> {
+    (TRIANGULATION_TABLE.iter())
+        .flat_map(|triangles| {
+            let padding = 16 - triangles.len();
+            (triangles.iter().map(|&x| x as u32)).chain((0..padding).map(|_| END))
+        })
+        .collect()
+}
@@ -0,0 +1,149 @@
+struct Uniform {
+    size: vec3u,
+    level_count: u32,
+    // Packed four to an element, see `gpu::MAX_LEVELS`
+    iso_levels: array<vec4f, 4>,
+    gradient_normals: u32,
+    // Most vertices that fit in the vertex buffer
+    capacity: u32
+}
+
+struct Vertex {
+    position: vec4f,
+    normal: vec3f,
+    level: u32,
+    scalar: f32
+}
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+@group(0) @binding(1) var<storage, read> field: array<f32>;
+@group(0) @binding(2) var<storage, read> edge_table: array<u32>;
+// Sixteen entries per cube, terminated by `END`
+@group(0) @binding(3) var<storage, read> triangulation_table: array<u32>;
+@group(0) @binding(4) var<storage, read_write> vertices: array<Vertex>;
+@group(0) @binding(5) var<storage, read_write> indices: array<u32>;
+// Vertices needed so far, which can exceed the capacity
+@group(0) @binding(6) var<storage, read_write> count: atomic<u32>;
+
+const END: u32 = 0xFFFFFFFFu;
+
+// See `GRID_POINTS`
+const GRID_POINTS = array(
+    vec3u(0, 0, 0), vec3u(1, 0, 0), vec3u(1, 0, 1), vec3u(0, 0, 1),
+    vec3u(0, 1, 0), vec3u(1, 1, 0), vec3u(1, 1, 1), vec3u(0, 1, 1)
+);
+
+// See `EDGE_CONNECTIONS`
+const EDGE_CONNECTIONS = array(
+    vec2u(0, 1), vec2u(1, 2), vec2u(2, 3), vec2u(3, 0),
+    vec2u(4, 5), vec2u(5, 6), vec2u(6, 7), vec2u(7, 4),
+    vec2u(0, 4), vec2u(1, 5), vec2u(2, 6), vec2u(3, 7)
+);
+
+// Polygonises a single cube into unshared vertices for every iso level, see
+// `gpu::reference`
+@compute
+@workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) pos: vec3u) {
+    if any(pos + 1u >= ctx.size) {
+        return;
+    }
+
+    var corners: array<f32, 8>;
+    for (var i = 0u; i < 8u; i++) {
+        corners[i] = field[index(pos + GRID_POINTS[i])];
+    }
+
+    for (var level = 0u; level < ctx.level_count; level++) {
+        polygonise(pos, corners, level);
+    }
+}
+
+fn polygonise(pos: vec3u, corners: array<f32, 8>, level: u32) {
+    let iso_level = ctx.iso_levels[level / 4u][level % 4u];
+    var cube_index = 0u;
+    for (var i = 0u; i < 8u; i++) {
+        cube_index |= u32(corners[i] < iso_level) << i;
+    }
+
+    let edges = edge_table[cube_index];
+    if edges == 0u {
+        return;
+    }
+
+    var points: array<vec3f, 12>;
+    var normals: array<vec3f, 12>;
+    for (var i = 0u; i < 12u; i++) {
+        if (edges & (1u << i)) == 0u {
+            continue;
+        }
+
+        let a = pos + GRID_POINTS[EDGE_CONNECTIONS[i].x];
+        let b = pos + GRID_POINTS[EDGE_CONNECTIONS[i].y];
+        let val_1 = corners[EDGE_CONNECTIONS[i].x];
+        let val_2 = corners[EDGE_CONNECTIONS[i].y];
+        let mu = (iso_level - val_1) / (val_2 - val_1);
+        points[i] = vec3f(a) + mu * (vec3f(b) - vec3f(a));
+        if ctx.gradient_normals != 0u {
+            let grad_1 = gradient(a);
+            let grad_2 = gradient(b);
+            normals[i] = grad_1 + mu * (grad_2 - grad_1);
+        }
+    }
+
+    let triangles = cube_index * 16u;
+    var vertex_count = 0u;
+    while triangulation_table[triangles + vertex_count] != END {
+        vertex_count++;
+    }
+
+    // Triangles past the capacity are dropped, so the buffers always start
+    // with as many whole triangles as fit
+    let base = atomicAdd(&count, vertex_count);
+    for (var i = 0u; i < vertex_count; i += 3u) {
+        if base + i + 3u > ctx.capacity {
+            return;
+        }
+
+        let edge = vec3(
+            triangulation_table[triangles + i],
+            triangulation_table[triangles + i + 1u],
+            triangulation_table[triangles + i + 2u]
+        );
+        let a = points[edge.x];
+        let face = safe_normalize(cross(points[edge.y] - a, points[edge.z] - a));
+
+        for (var j = 0u; j < 3u; j++) {
+            var normal = face;
+            if ctx.gradient_normals != 0u {
+                normal = safe_normalize(normals[edge[j]]);
+            }
+
+            let vertex = base + i + j;
+            vertices[vertex] = Vertex(vec4(points[edge[j]], 1.0), normal, level, 0.0);
+            indices[vertex] = vertex;
+        }
+    }
+}
+
+// See `marching_cubes::gradient`
+fn gradient(pos: vec3u) -> vec3f {
+    var gradient = vec3(0.0);
+    for (var axis = 0u; axis < 3u; axis++) {
+        var low = pos;
+        var high = pos;
+        low[axis] = max(low[axis], 1u) - 1u;
+        high[axis] = min(high[axis] + 1u, ctx.size[axis] - 1u);
+
+        let span = f32(high[axis] - low[axis]);
+        gradient[axis] = (field[index(high)] - field[index(low)]) / span;
+    }
+
+    return gradient;
+}
+
+// Like `Vector3::try_normalize`, returning zero for zero length vectors
+fn safe_normalize(x: vec3f) -> vec3f {
+    let norm = length(x);
+    return select(vec3(0.0), x / norm, norm > 0.0);
+}
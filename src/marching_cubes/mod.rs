@@ -1,15 +1,26 @@
 //! Implementation of <https://paulbourke.net/geometry/polygonise>.
 
-use std::collections::HashMap;
+use std::fmt::Display;
 
 use compute::export::nalgebra::Vector3;
 use itertools::Itertools;
-use ordered_float::OrderedFloat;
+use rayon::{
+    iter::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+        IntoParallelRefMutIterator, ParallelIterator,
+    },
+    slice::ParallelSliceMut,
+};
+use surface_nets::surface_nets;
 use table::{EDGE_TABLE, TRIANGULATION_TABLE};
+use tetrahedra::marching_tetrahedra;
 
 use crate::vertex::Vertex;
 
+pub mod gpu;
+mod surface_nets;
 mod table;
+mod tetrahedra;
 
 #[rustfmt::skip]
 const EDGE_CONNECTIONS: [(usize, usize); 12] = [
@@ -18,6 +29,44 @@ const EDGE_CONNECTIONS: [(usize, usize); 12] = [
     (0, 4), (1, 5), (2, 6), (3, 7)
 ];
 
+/// How vertex normals are computed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Normals {
+    /// Average of the normals of the faces touching each vertex.
+    Face,
+    /// Central difference gradient of the scalar field, interpolated along
+    /// the edge the same way as the vertex position.
+    Gradient,
+}
+
+/// Algorithm used to turn a scalar field into a surface.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mesher {
+    /// Classic marching cubes. Fast, but the triangulations chosen for
+    /// ambiguous faces don't always agree between neighbouring cubes, which
+    /// leaves cracks in the surface.
+    Cubes,
+    /// Marching tetrahedra, which has no ambiguous cases and so is always
+    /// watertight, at the cost of roughly twice as many triangles.
+    Tetrahedra,
+    /// Naive surface nets, which uses fewer vertices than marching cubes
+    /// and makes no sliver triangles, but rounds off sharp features.
+    SurfaceNets,
+}
+
+/// Marks grid edges the surface doesn't cross.
+const NO_VERTEX: u32 = u32::MAX;
+/// Marks grid edges crossing the surface at one of their end points, which
+/// use that point's vertex instead.
+const ON_POINT: u32 = u32::MAX - 1;
+
+/// Edges along each axis, as used by [`marching_cubes`].
+const AXES: [Vector3<usize>; 3] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, 0, 1),
+];
+
 const GRID_POINTS: [Vector3<usize>; 8] = [
     Vector3::new(0, 0, 0),
     Vector3::new(1, 0, 0),
@@ -29,72 +78,339 @@ const GRID_POINTS: [Vector3<usize>; 8] = [
     Vector3::new(0, 1, 1),
 ];
 
+impl Mesher {
+    pub const ALL: [Self; 3] = [Self::Cubes, Self::Tetrahedra, Self::SurfaceNets];
+
+    pub fn mesh(
+        self,
+        scalar_field: &[f32],
+        size: Vector3<usize>,
+        iso_level: f32,
+        normals: Normals,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        match self {
+            Self::Cubes => marching_cubes(scalar_field, size, &[iso_level], normals),
+            Self::Tetrahedra => marching_tetrahedra(scalar_field, size, iso_level, normals),
+            Self::SurfaceNets => surface_nets(scalar_field, size, iso_level, normals),
+        }
+    }
+
+    /// Meshes several iso-levels of the same field into a single buffer,
+    /// setting the level of every vertex to the index of its surface.
+    ///
+    /// Marching cubes classifies every level in the same pass over the grid.
+    /// The other meshers run a pass per level in parallel.
+    pub fn mesh_levels(
+        self,
+        scalar_field: &[f32],
+        size: Vector3<usize>,
+        iso_levels: &[f32],
+        normals: Normals,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        if self == Self::Cubes {
+            return marching_cubes(scalar_field, size, iso_levels, normals);
+        }
+
+        let meshes = (iso_levels.par_iter())
+            .map(|&iso_level| self.mesh(scalar_field, size, iso_level, normals))
+            .collect::<Vec<_>>();
+
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        for (level, (mesh_vertices, mesh_indices)) in meshes.into_iter().enumerate() {
+            let offset = vertices.len() as u32;
+            indices.extend(mesh_indices.into_iter().map(|x| x + offset));
+            vertices.extend(mesh_vertices.into_iter().map(|x| Vertex {
+                level: level as u32,
+                ..x
+            }));
+        }
+
+        (vertices, indices)
+    }
+}
+
+impl Display for Mesher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Cubes => "Marching Cubes",
+            Self::Tetrahedra => "Marching Tetrahedra",
+            Self::SurfaceNets => "Surface Nets",
+        })
+    }
+}
+
+/// Polygonises the grid one x slab at a time in parallel, sharing vertices
+/// between cubes through [`EdgeVertices`]. Triangles left with zero area by
+/// its welding are dropped.
+///
+/// Every cube is classified against each of the `iso_levels` while its
+/// corners are loaded, with the level of each vertex set to the index of its
+/// surface.
 pub fn marching_cubes(
     scalar_field: &[f32],
     size: Vector3<usize>,
-    iso_level: f32,
+    iso_levels: &[f32],
+    normals: Normals,
 ) -> (Vec<Vertex>, Vec<u32>) {
-    let mut vertex_lookup = HashMap::<Vector3<OrderedFloat<f32>>, u32>::new();
-    let mut vertices = Vec::<Vertex>::new();
-    let mut indices = Vec::new();
+    let stride = Vector3::new(size.y * size.z, size.z, 1);
+    let edges = EdgeVertices::new(scalar_field, size, iso_levels, normals, &AXES);
 
-    for ((x, y), z) in (0..size.x - 1)
-        .cartesian_product(0..size.y - 1)
-        .cartesian_product(0..size.z - 1)
-    {
-        let mut grid = [(Vector3::zeros(), 0.0); 8];
-        let mut cube_index = 0;
+    let edge_keys = EDGE_CONNECTIONS.map(|(p1, p2)| {
+        let (p1, p2) = (GRID_POINTS[p1], GRID_POINTS[p2]);
+        let axis = (0..3).find(|&i| p1[i] != p2[i]).unwrap();
+        (p1.inf(&p2).dot(&stride), axis)
+    });
 
-        for (i, offset) in GRID_POINTS.iter().enumerate() {
-            let pos = Vector3::new(x, y, z) + offset;
+    let indices = (0..size.x.saturating_sub(1))
+        .into_par_iter()
+        .flat_map_iter(|x| {
+            let mut indices = Vec::new();
+            for (y, z) in (0..size.y - 1).cartesian_product(0..size.z - 1) {
+                let idx = Vector3::new(x, y, z).dot(&stride);
+                let corners = GRID_POINTS.map(|offset| scalar_field[idx + offset.dot(&stride)]);
 
-            let index = pos.x * size.y * size.z + pos.y * size.z + pos.z;
-            let value = scalar_field[index];
+                for (level, &iso_level) in iso_levels.iter().enumerate() {
+                    let mut cube_index = 0;
+                    for (i, value) in corners.iter().enumerate() {
+                        cube_index |= ((*value < iso_level) as usize) << i;
+                    }
 
-            grid[i] = (pos.map(|x| x as f32), value);
-            cube_index |= ((value < iso_level) as usize) << i;
-        }
+                    if EDGE_TABLE[cube_index] == 0 {
+                        continue;
+                    }
+
+                    for triangle in TRIANGULATION_TABLE[cube_index].chunks(3) {
+                        let [a, b, c] = [0, 1, 2].map(|i| {
+                            let (offset, axis) = edge_keys[triangle[i] as usize];
+                            edges.get(idx + offset, axis, level)
+                        });
+                        if a != b && b != c && c != a {
+                            indices.extend([a, b, c]);
+                        }
+                    }
+                }
+            }
+            indices
+        })
+        .collect::<Vec<_>>();
+
+    (edges.finish(&indices, normals), indices)
+}
 
-        let edge = EDGE_TABLE[cube_index];
-        let mut vertlist = [Vector3::zeros(); 12];
-        for (i, &(p1, p2)) in EDGE_CONNECTIONS
-            .iter()
+/// The vertices where the surface crosses grid edges, for the edges running
+/// from every grid point along each of a set of `directions`. Vertices are
+/// keyed by the edge they lie on rather than by position, so each one is
+/// created exactly once and shared by every cell touching that edge. Each of
+/// the `iso_levels` has its own surface, found in the same pass over the
+/// grid, whose vertices are tagged with the index of the level.
+///
+/// A grid point exactly at the iso level puts a vertex on top of it for
+/// every crossing edge touching it, so those edges are instead keyed by the
+/// point itself.
+pub struct EdgeVertices<'a> {
+    scalar_field: &'a [f32],
+    iso_levels: &'a [f32],
+    stride: Vector3<usize>,
+    directions: &'a [Vector3<usize>],
+
+    vertices: Vec<Vertex>,
+    edges: Vec<u32>,
+    points: Vec<u32>,
+}
+
+impl<'a> EdgeVertices<'a> {
+    /// Finds the vertices one x slab at a time in parallel. Every slab owns
+    /// the edges and points starting in it, so no two slabs write the same
+    /// entry.
+    pub fn new(
+        scalar_field: &'a [f32],
+        size: Vector3<usize>,
+        iso_levels: &'a [f32],
+        normals: Normals,
+        directions: &'a [Vector3<usize>],
+    ) -> Self {
+        let slab = size.y * size.z;
+        let stride = Vector3::new(slab, size.z, 1);
+        let (count, levels) = (directions.len(), iso_levels.len());
+        let gradient = |pos: Vector3<usize>| match normals {
+            Normals::Face => Vector3::zeros(),
+            Normals::Gradient => gradient(scalar_field, size, pos),
+        };
+
+        let mut edges = vec![NO_VERTEX; slab * count * levels * size.x];
+        let mut points = vec![NO_VERTEX; slab * levels * size.x];
+        let slab_vertices = (edges.par_chunks_mut(slab * count * levels))
+            .zip(points.par_chunks_mut(slab * levels))
             .enumerate()
-            .filter(|(i, _)| edge & (1 << i) != 0)
-        {
-            vertlist[i] = vertex_interp(iso_level, grid[p1], grid[p2]);
-        }
+            .map(|(x, (edges, points))| {
+                let mut vertices = Vec::new();
+                for (y, z) in (0..size.y).cartesian_product(0..size.z) {
+                    let pos = Vector3::new(x, y, z);
+                    let idx = pos.dot(&stride);
+                    let local = idx - x * slab;
+
+                    for (level, &iso_level) in iso_levels.iter().enumerate() {
+                        let inside = |idx: usize| scalar_field[idx] < iso_level;
+                        let on_surface = |idx: usize| scalar_field[idx] == iso_level;
+                        let vertex = |position: Vector3<f32>, normal| Vertex {
+                            level: level as u32,
+                            ..Vertex::new(position.push(1.0), normal)
+                        };
+
+                        // Points on the surface only need a vertex if one of
+                        // their neighbours is inside, making an edge between
+                        // them cross.
+                        if on_surface(idx)
+                            && directions.iter().any(|dir| {
+                                let offset = dir.dot(&stride);
+                                ((0..3).all(|i| pos[i] + dir[i] < size[i]) && inside(idx + offset))
+                                    || ((0..3).all(|i| pos[i] >= dir[i]) && inside(idx - offset))
+                            })
+                        {
+                            points[local * levels + level] = vertices.len() as u32;
+                            vertices.push(vertex(pos.map(|x| x as f32), gradient(pos)));
+                        }
+
+                        for (i, dir) in directions.iter().enumerate() {
+                            let next_pos = pos + dir;
+                            if (0..3).any(|i| next_pos[i] >= size[i]) {
+                                continue;
+                            }
 
-        let triangles = TRIANGULATION_TABLE[cube_index];
-        for triangle in triangles.chunks(3) {
-            let normal = (vertlist[triangle[1] as usize] - vertlist[triangle[0] as usize])
-                .cross(&(vertlist[triangle[2] as usize] - vertlist[triangle[0] as usize]))
-                .normalize();
-
-            let get_point_idx = |vert: u8| {
-                let point = vertlist[vert as usize];
-                let orderd = point.map(OrderedFloat);
-                if let Some(&idx) = vertex_lookup.get(&orderd) {
-                    vertices[idx as usize].normal += normal;
-                    return idx;
+                            let next = next_pos.dot(&stride);
+                            if inside(idx) == inside(next) {
+                                continue;
+                            }
+
+                            let edge = (local * count + i) * levels + level;
+                            if on_surface(idx) || on_surface(next) {
+                                edges[edge] = ON_POINT;
+                                continue;
+                            }
+
+                            let (val_1, val_2) = (scalar_field[idx], scalar_field[next]);
+                            let [point, normal] = [
+                                (pos.map(|x| x as f32), next_pos.map(|x| x as f32)),
+                                (gradient(pos), gradient(next_pos)),
+                            ]
+                            .map(|(a, b)| vertex_interp(iso_level, (a, val_1), (b, val_2)));
+
+                            edges[edge] = vertices.len() as u32;
+                            vertices.push(vertex(point, normal));
+                        }
+                    }
                 }
+                vertices
+            })
+            .collect::<Vec<_>>();
+
+        let vertices = merge_slabs(
+            slab_vertices,
+            [
+                (&mut edges, slab * count * levels),
+                (&mut points, slab * levels),
+            ],
+        );
+        Self {
+            scalar_field,
+            iso_levels,
+            stride,
+            directions,
+
+            vertices,
+            edges,
+            points,
+        }
+    }
 
-                let idx = vertices.len() as u32;
-                vertices.push(Vertex::new(point.push(1.0), normal));
-                vertex_lookup.insert(orderd, idx);
-                idx
-            };
+    /// Index of the vertex on the edge from grid point `idx` along
+    /// `directions[direction]` on the surface of `iso_levels[level]`. The
+    /// edge must cross that surface.
+    pub fn get(&self, idx: usize, direction: usize, level: usize) -> u32 {
+        let levels = self.iso_levels.len();
+        match self.edges[(idx * self.directions.len() + direction) * levels + level] {
+            ON_POINT if self.scalar_field[idx] == self.iso_levels[level] => {
+                self.points[idx * levels + level]
+            }
+            ON_POINT => {
+                let next = idx + self.directions[direction].dot(&self.stride);
+                self.points[next * levels + level]
+            }
+            vertex => vertex,
+        }
+    }
+
+    pub fn position(&self, vertex: u32) -> Vector3<f32> {
+        self.vertices[vertex as usize].position.xyz()
+    }
+
+    /// Returns the vertices with normals computed by [`finish_normals`].
+    pub fn finish(self, indices: &[u32], normals: Normals) -> Vec<Vertex> {
+        let mut vertices = self.vertices;
+        finish_normals(&mut vertices, indices, normals);
+        vertices
+    }
+}
+
+/// Concatenates the vertices found by each x slab, turning the slab local
+/// vertex indices in each of `keys` into indices into the final buffer. The
+/// keys are split into chunks of the given length, one per slab.
+fn merge_slabs<const N: usize>(
+    slab_vertices: Vec<Vec<Vertex>>,
+    keys: [(&mut [u32], usize); N],
+) -> Vec<Vertex> {
+    let offsets = (slab_vertices.iter())
+        .scan(0, |offset, vertices| {
+            let start = *offset;
+            *offset += vertices.len() as u32;
+            Some(start)
+        })
+        .collect::<Vec<_>>();
 
-            let points = triangle.iter().copied().map(get_point_idx);
-            indices.extend(points);
+    for (keys, len) in keys {
+        keys.par_chunks_mut(len)
+            .zip(&offsets)
+            .for_each(|(keys, &offset)| {
+                keys.iter_mut()
+                    .filter(|x| **x < ON_POINT)
+                    .for_each(|x| *x += offset);
+            });
+    }
+
+    slab_vertices.into_iter().flatten().collect()
+}
+
+/// Normalizes the vertex normals, first accumulating the normals of the
+/// given triangles onto them if using face normals.
+fn finish_normals(vertices: &mut [Vertex], indices: &[u32], normals: Normals) {
+    for triangle in indices.chunks(3).filter(|_| normals == Normals::Face) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position.xyz());
+        let Some(normal) = (b - a).cross(&(c - a)).try_normalize(0.0) else {
+            continue;
+        };
+
+        for &idx in triangle {
+            vertices[idx as usize].normal += normal;
         }
     }
 
     vertices
-        .iter_mut()
-        .for_each(|v| v.normal = v.normal.normalize());
+        .par_iter_mut()
+        .for_each(|v| v.normal = v.normal.try_normalize(0.0).unwrap_or_default());
+}
+
+/// Central difference gradient of the field at a grid point, falling back to
+/// one sided differences on the faces of the grid.
+fn gradient(scalar_field: &[f32], size: Vector3<usize>, pos: Vector3<usize>) -> Vector3<f32> {
+    let index = |pos: Vector3<usize>| pos.x * size.y * size.z + pos.y * size.z + pos.z;
+    Vector3::from_fn(|axis, _| {
+        let (mut low, mut high) = (pos, pos);
+        low[axis] = low[axis].saturating_sub(1);
+        high[axis] = (high[axis] + 1).min(size[axis] - 1);
 
-    (vertices, indices)
+        let span = (high[axis] - low[axis]) as f32;
+        (scalar_field[index(high)] - scalar_field[index(low)]) / span
+    })
 }
 
 fn vertex_interp(
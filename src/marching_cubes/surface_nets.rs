@@ -0,0 +1,166 @@
+//! Naive surface nets, the dual of marching cubes. Instead of putting
+//! vertices on the grid edges, each cube the surface passes through gets a
+//! single vertex at the average of its edge crossings, and every crossing
+//! edge becomes a quad joining the vertices of the four cubes around it.
+//! Vertices are shared by more triangles than with marching cubes, and since
+//! they sit near the middle of their cubes there are no slivers.
+
+use compute::export::nalgebra::Vector3;
+use itertools::Itertools;
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+
+use super::{
+    finish_normals, gradient, merge_slabs, vertex_interp, Normals, EDGE_CONNECTIONS, GRID_POINTS,
+    NO_VERTEX,
+};
+use crate::vertex::Vertex;
+
+pub fn surface_nets(
+    scalar_field: &[f32],
+    size: Vector3<usize>,
+    iso_level: f32,
+    normals: Normals,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let slab = size.y * size.z;
+    let stride = Vector3::new(slab, size.z, 1);
+    let inside = |idx: usize| scalar_field[idx] < iso_level;
+    let gradient = |pos: Vector3<usize>| match normals {
+        Normals::Face => Vector3::zeros(),
+        Normals::Gradient => gradient(scalar_field, size, pos),
+    };
+
+    // Places a vertex in every cube with corners on both sides of the
+    // surface. Cubes are identified by their lowest corner.
+    let mut cube_vertices = vec![NO_VERTEX; slab * size.x];
+    let slab_vertices = (cube_vertices.par_chunks_mut(slab))
+        .enumerate()
+        .map(|(x, cubes)| {
+            let mut vertices = Vec::new();
+            if x + 1 >= size.x {
+                return vertices;
+            }
+
+            for (y, z) in (0..size.y - 1).cartesian_product(0..size.z - 1) {
+                let pos = Vector3::new(x, y, z);
+                let idx = pos.dot(&stride);
+                let corners = GRID_POINTS.map(|offset| inside(idx + offset.dot(&stride)));
+                if corners.iter().all_equal() {
+                    continue;
+                }
+
+                let (mut point, mut normal, mut crossings) =
+                    (Vector3::zeros(), Vector3::zeros(), 0);
+                for (a, b) in EDGE_CONNECTIONS
+                    .into_iter()
+                    .filter(|&(a, b)| corners[a] != corners[b])
+                {
+                    let (a, b) = (pos + GRID_POINTS[a], pos + GRID_POINTS[b]);
+                    let (val_1, val_2) =
+                        (scalar_field[a.dot(&stride)], scalar_field[b.dot(&stride)]);
+                    point += vertex_interp(
+                        iso_level,
+                        (a.map(|x| x as f32), val_1),
+                        (b.map(|x| x as f32), val_2),
+                    );
+                    normal += vertex_interp(iso_level, (gradient(a), val_1), (gradient(b), val_2));
+                    crossings += 1;
+                }
+
+                cubes[idx - x * slab] = vertices.len() as u32;
+                let point = point / crossings as f32;
+                vertices.push(Vertex::new(point.push(1.0), normal / crossings as f32));
+            }
+            vertices
+        })
+        .collect::<Vec<_>>();
+    let mut vertices = merge_slabs(slab_vertices, [(&mut cube_vertices, slab)]);
+
+    // Joins the four cubes around every crossing edge with a quad. Edges on
+    // the faces of the grid are missing some of their cubes and are skipped.
+    let indices = (0..size.x)
+        .into_par_iter()
+        .flat_map_iter(|x| {
+            let mut indices = Vec::new();
+            for (y, z) in (0..size.y).cartesian_product(0..size.z) {
+                let pos = Vector3::new(x, y, z);
+                let idx = pos.dot(&stride);
+
+                for axis in 0..3 {
+                    let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+                    if pos[axis] + 1 >= size[axis]
+                        || !(1..size[b] - 1).contains(&pos[b])
+                        || !(1..size[c] - 1).contains(&pos[c])
+                        || inside(idx) == inside(idx + stride[axis])
+                    {
+                        continue;
+                    }
+
+                    // Counter clockwise around the edge when looking down
+                    // it, so the quad faces along the axis.
+                    let mut quad = [(1, 1), (0, 1), (0, 0), (1, 0)]
+                        .map(|(db, dc)| cube_vertices[idx - db * stride[b] - dc * stride[c]]);
+
+                    // Face from the inside end of the edge to the outside one
+                    if !inside(idx) {
+                        quad.reverse();
+                    }
+
+                    // Split along the shorter diagonal for better shaped triangles
+                    let position = |i: usize| vertices[quad[i] as usize].position.xyz();
+                    let [q0, q1, q2, q3] = quad;
+                    if (position(0) - position(2)).norm_squared()
+                        <= (position(1) - position(3)).norm_squared()
+                    {
+                        indices.extend([q0, q1, q2, q0, q2, q3]);
+                    } else {
+                        indices.extend([q1, q2, q3, q1, q3, q0]);
+                    }
+                }
+            }
+            indices
+        })
+        .collect::<Vec<_>>();
+
+    finish_normals(&mut vertices, &indices, normals);
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use compute::export::nalgebra::Vector3;
+    use itertools::Itertools;
+
+    use super::surface_nets;
+    use crate::marching_cubes::{Normals, GRID_POINTS};
+
+    #[test]
+    fn vertex_per_crossing_cube() {
+        let size = Vector3::new(12, 14, 16);
+        let stride = Vector3::new(size.y * size.z, size.z, 1);
+        let field = (0..size.product())
+            .map(|i| {
+                let pos = Vector3::new(i / stride.x, i / size.z % size.y, i % size.z);
+                let pos = pos.map(|x| x as f32);
+                (pos.x * 0.7).sin() + (pos.y * 0.5).cos() * pos.z * 0.1
+            })
+            .collect::<Vec<_>>();
+
+        let crossing = (0..size.x - 1)
+            .cartesian_product(0..size.y - 1)
+            .cartesian_product(0..size.z - 1)
+            .filter(|&((x, y), z)| {
+                let idx = Vector3::new(x, y, z).dot(&stride);
+                !(GRID_POINTS.iter())
+                    .map(|offset| field[idx + offset.dot(&stride)] < 0.2)
+                    .all_equal()
+            })
+            .count();
+
+        let (vertices, _) = surface_nets(&field, size, 0.2, Normals::Face);
+        assert!(crossing > 0);
+        assert_eq!(vertices.len(), crossing);
+    }
+}
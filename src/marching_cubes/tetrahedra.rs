@@ -0,0 +1,157 @@
+//! Marching tetrahedra, which splits every cube into six tetrahedra sharing
+//! its main diagonal. The iso-surface is a single plane inside each
+//! tetrahedron so there are no ambiguous cases, and because every cube is
+//! split the same way the diagonals on shared faces always line up, making
+//! the surface watertight.
+
+use compute::export::nalgebra::Vector3;
+use itertools::Itertools;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{EdgeVertices, Normals, GRID_POINTS};
+use crate::vertex::Vertex;
+
+/// Every edge of the tetrahedra goes from a grid point along one of these,
+/// being either a cube edge, a face diagonal or the main diagonal.
+const DIRECTIONS: [Vector3<usize>; 7] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(1, 1, 0),
+    Vector3::new(1, 0, 1),
+    Vector3::new(0, 1, 1),
+    Vector3::new(1, 1, 1),
+];
+
+/// Corners of each tetrahedron as indices into [`GRID_POINTS`]. They all
+/// share the diagonal from corner 0 to 6 and walk around it, with each pair
+/// of neighbouring corners differing along a single axis.
+#[rustfmt::skip]
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 6, 1, 2], [0, 6, 2, 3], [0, 6, 3, 7],
+    [0, 6, 7, 4], [0, 6, 4, 5], [0, 6, 5, 1],
+];
+
+pub fn marching_tetrahedra(
+    scalar_field: &[f32],
+    size: Vector3<usize>,
+    iso_level: f32,
+    normals: Normals,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let stride = Vector3::new(size.y * size.z, size.z, 1);
+    let inside = |idx: usize| scalar_field[idx] < iso_level;
+    let iso_levels = [iso_level];
+    let edges = EdgeVertices::new(scalar_field, size, &iso_levels, normals, &DIRECTIONS);
+
+    // The grid point offset and direction of the edge between every pair of
+    // cube corners. Only pairs sharing a tetrahedron are ever looked up.
+    let edge_keys: [[(usize, usize); 8]; 8] = std::array::from_fn(|a| {
+        std::array::from_fn(|b| {
+            let (a, b) = (GRID_POINTS[a], GRID_POINTS[b]);
+            let low = a.inf(&b);
+            let direction = DIRECTIONS.iter().position(|&x| x == a.sup(&b) - low);
+            (low.dot(&stride), direction.unwrap_or_default())
+        })
+    });
+
+    let indices = (0..size.x.saturating_sub(1))
+        .into_par_iter()
+        .flat_map_iter(|x| {
+            let mut indices = Vec::new();
+            for (y, z) in (0..size.y - 1).cartesian_product(0..size.z - 1) {
+                let idx = Vector3::new(x, y, z).dot(&stride);
+                let corners = GRID_POINTS.map(|offset| inside(idx + offset.dot(&stride)));
+                if corners.iter().all_equal() {
+                    continue;
+                }
+
+                let vertex = |a: usize, b: usize| {
+                    let (offset, direction) = edge_keys[a][b];
+                    edges.get(idx + offset, direction, 0)
+                };
+
+                for tetrahedron in TETRAHEDRA {
+                    let (inner, outer): (Vec<_>, Vec<_>) =
+                        tetrahedron.iter().partition(|&&i| corners[i]);
+
+                    let polygon = match (&inner[..], &outer[..]) {
+                        ([], _) | (_, []) => continue,
+                        (&[a], others) | (others, &[a]) => {
+                            vec![
+                                vertex(a, others[0]),
+                                vertex(a, others[1]),
+                                vertex(a, others[2]),
+                            ]
+                        }
+                        (&[a, b], &[c, d]) => {
+                            vec![vertex(a, c), vertex(a, d), vertex(b, d), vertex(b, c)]
+                        }
+                        _ => unreachable!(),
+                    };
+
+                    // Wind the triangles to face from the inside corners to
+                    // the outside ones, so face normals are consistent.
+                    let center = |corners: &[usize]| {
+                        corners
+                            .iter()
+                            .map(|&i| GRID_POINTS[i].map(|x| x as f32))
+                            .sum::<Vector3<f32>>()
+                            / corners.len() as f32
+                    };
+                    let outward = center(&outer) - center(&inner);
+
+                    for i in 1..polygon.len() - 1 {
+                        let [a, mut b, mut c] = [polygon[0], polygon[i], polygon[i + 1]];
+                        if a == b || b == c || c == a {
+                            continue;
+                        }
+
+                        let [pa, pb, pc] = [a, b, c].map(|x| edges.position(x));
+                        if (pb - pa).cross(&(pc - pa)).dot(&outward) < 0.0 {
+                            (b, c) = (c, b);
+                        }
+                        indices.extend([a, b, c]);
+                    }
+                }
+            }
+            indices
+        })
+        .collect::<Vec<_>>();
+
+    (edges.finish(&indices, normals), indices)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use compute::export::nalgebra::Vector3;
+
+    use super::marching_tetrahedra;
+    use crate::marching_cubes::Normals;
+
+    #[test]
+    fn sphere_is_closed() {
+        let size = Vector3::repeat(16);
+        let center = Vector3::new(7.3, 7.6, 8.1);
+        let field = (0..size.product())
+            .map(|i| {
+                let pos = Vector3::new(i / (size.y * size.z), i / size.z % size.y, i % size.z);
+                (pos.map(|x| x as f32) - center).norm()
+            })
+            .collect::<Vec<_>>();
+
+        let (_, indices) = marching_tetrahedra(&field, size, 5.3, Normals::Face);
+        assert!(!indices.is_empty());
+
+        // Every edge of a closed surface is shared by pairs of triangles
+        let mut edges = HashMap::<_, usize>::new();
+        for triangle in indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        assert!(edges.values().all(|count| count % 2 == 0));
+    }
+}
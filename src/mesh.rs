@@ -0,0 +1,100 @@
+use anyhow::Result;
+use compute::{
+    bindings::{IndexBuffer, VertexBuffer},
+    gpu::Gpu,
+};
+
+use crate::vertex::Vertex;
+
+/// The most entries the vertex and index buffers can grow to. Vertex buffers
+/// are also bound as storage by the GPU mesher, so this is as many vertices
+/// as fit in the default 128 MiB storage buffer binding limit.
+pub const MAX_ENTRIES: u64 = (128 << 20) / 48;
+
+/// Entries a new mesh has room for before it first grows.
+const INITIAL_ENTRIES: u64 = 1 << 16;
+
+/// Vertex and index buffers that are reallocated to fit bigger meshes, up to
+/// [`MAX_ENTRIES`]. Meshes that still don't fit are truncated.
+pub struct Mesh {
+    pub index: IndexBuffer,
+    pub vertex: VertexBuffer<Vertex>,
+    /// Number of indices to draw.
+    pub count: u32,
+    /// Entries needed by the last mesh, if it didn't fit and was truncated.
+    pub truncated: Option<u64>,
+    capacity: u64,
+}
+
+impl Mesh {
+    pub fn new(gpu: &Gpu) -> Result<Self> {
+        Self::with_capacity(gpu, INITIAL_ENTRIES)
+    }
+
+    pub fn with_capacity(gpu: &Gpu, capacity: u64) -> Result<Self> {
+        Ok(Self {
+            index: gpu.create_index_empty(capacity),
+            vertex: gpu.create_vertex_empty(capacity)?,
+            count: 0,
+            truncated: None,
+            capacity,
+        })
+    }
+
+    /// Entries there is room for in both buffers.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Reallocates the buffers if they can't hold `entries`, returning
+    /// whether they were, in which case anything bound to the old buffers
+    /// needs to be recreated. The old contents are not kept.
+    pub fn reserve(&mut self, gpu: &Gpu, entries: u64) -> Result<bool> {
+        if entries <= self.capacity || self.capacity == MAX_ENTRIES {
+            return Ok(false);
+        }
+
+        let capacity = entries.next_power_of_two().min(MAX_ENTRIES);
+        *self = Self::with_capacity(gpu, capacity)?;
+        Ok(true)
+    }
+
+    /// Uploads a mesh, growing the buffers to fit it. If it's still too big,
+    /// the vertices past the capacity are dropped along with every triangle
+    /// using them. Returns whether the buffers were reallocated, like
+    /// [`Mesh::reserve`].
+    pub fn upload(&mut self, gpu: &Gpu, vertices: &[Vertex], indices: &[u32]) -> Result<bool> {
+        let entries = vertices.len().max(indices.len()) as u64;
+        let reallocated = self.reserve(gpu, entries)?;
+        self.truncated = (entries > self.capacity).then_some(entries);
+
+        let capacity = self.capacity as usize;
+        let vertices = &vertices[..vertices.len().min(capacity)];
+        let kept;
+        let indices = match self.truncated {
+            Some(_) => {
+                kept = (indices.chunks_exact(3))
+                    .filter(|x| x.iter().all(|&i| (i as usize) < vertices.len()))
+                    .take(capacity / 3)
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+                &kept[..]
+            }
+            None => indices,
+        };
+
+        self.count = indices.len() as u32;
+        self.vertex.upload(vertices)?;
+        self.index.upload(indices)?;
+        Ok(reallocated)
+    }
+
+    /// Sets the count after `entries` unshared vertices were written straight
+    /// into the buffers, such as by the GPU mesher, which only writes whole
+    /// triangles that fit.
+    pub fn written(&mut self, entries: u64) {
+        self.truncated = (entries > self.capacity).then_some(entries);
+        self.count = entries.min(self.capacity / 3 * 3) as u32;
+    }
+}
@@ -0,0 +1,7 @@
+
+@fragment
+fn frag(in: VertexOutput) -> @location(0) vec4f {
+    let render = ctx.render;
+    let value = render.ambiant + render.intensity * facing(in);
+    return vec4(base_color(in).rgb * value, 1.0);
+}
@@ -1,23 +1,42 @@
 struct VertexInput {
     @location(0) pos: vec4f,
-    @location(1) normal: vec3f
+    @location(1) normal: vec3f,
+    @location(2) level: u32,
+    @location(3) scalar: f32
 }
 
 struct VertexOutput {
     @builtin(position) pos: vec4f,
     @location(0) normal: vec3f,
-    @location(1) world_position: vec3f
+    @location(1) world_position: vec3f,
+    @location(2) @interpolate(flat) level: u32,
+    @location(3) scalar: f32
 };
 
 struct Uniform {
     view_projection: mat4x4f,
     camera_dir: vec3f,
+    render: RenderConfig,
+    // Two per iso-level, see `MAX_ISO_LEVELS`
+    surfaces: array<vec4f, 16>,
 
+    // See `colormap::STOPS`
+    color_map: array<vec3f, 9>,
+    scalar_range: vec2f,
+    color_by_scalar: u32
+}
+
+struct RenderConfig {
     ambiant: f32,
     intensity: f32,
-    edge_falloff: f32
+    edge_falloff: f32,
+
+    obstacle: vec4f
 }
 
+// Level of vertices on obstacle surfaces
+const OBSTACLE: u32 = 0xFFFFFFFFu;
+
 @group(0) @binding(0) var<uniform> ctx: Uniform;
 
 @vertex
@@ -25,12 +44,31 @@ fn vert(in: VertexInput) -> VertexOutput {
     return VertexOutput(
         ctx.view_projection * in.pos,
         in.normal,
-        in.pos.xyz
+        in.pos.xyz,
+        in.level,
+        in.scalar
     );
 }
 
-@fragment
-fn frag(in: VertexOutput) -> @location(0) vec4f {
-    let opacity = abs(dot(in.normal, ctx.camera_dir));
-    return vec4(ctx.ambiant + ctx.intensity * (1.0 - pow(opacity, ctx.edge_falloff)));
+// The fragment entry point is appended from either `opaque.wgsl` or
+// `accumulate.wgsl`, see `transparency::surface_shader`.
+
+fn base_color(in: VertexOutput) -> vec4f {
+    if in.level == OBSTACLE {
+        return ctx.render.obstacle;
+    }
+
+    let color = ctx.surfaces[in.level];
+    if ctx.color_by_scalar != 0u {
+        let range = ctx.scalar_range;
+        let t = (in.scalar - range.x) / max(range.y - range.x, 1e-6);
+        return vec4(color_map(t), color.a);
+    }
+
+    return color;
+}
+
+// How much of the camera's view direction the surface faces, one when head on
+fn facing(in: VertexOutput) -> f32 {
+    return abs(dot(in.normal, ctx.camera_dir));
 }
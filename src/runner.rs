@@ -0,0 +1,256 @@
+//! Runs the solver continuously on a background thread. The simulation is
+//! shared with the UI behind a mutex, which the solver thread only holds for
+//! one step at a time, so the UI never waits on more than a single tick.
+
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use anyhow::{bail, Error, Result};
+use compute::gpu::Gpu;
+
+use crate::simulation::{
+    gpu::{GpuSolver, Solver},
+    Simulation,
+};
+
+/// Controls for running the simulation, along with the thread that ticks it.
+pub struct Runner {
+    handle: Handle,
+
+    pub running: bool,
+    /// Most steps the solver thread is asked to take each frame. It takes
+    /// fewer if it can't keep up.
+    pub steps_per_frame: usize,
+    /// Simulated time in seconds to pause at, if any.
+    pub target_time: Option<f32>,
+    /// Steps between remeshes while running.
+    pub remesh_every: usize,
+    /// Step the iso-surfaces were last meshed at.
+    pub meshed_step: usize,
+}
+
+/// The simulation and whichever solver advances it.
+pub struct Shared {
+    pub simulation: Simulation,
+    pub solver: Solver,
+    /// Created the first time the GPU solver is chosen.
+    pub gpu_solver: Option<GpuSolver>,
+    /// The last error from the GPU solver. Nothing runs until it's cleared by
+    /// resetting or changing solver.
+    pub error: Option<Error>,
+
+    /// Steps left for the solver thread to take.
+    pending: usize,
+    /// If the GPU solver has ticked since its states were last copied back
+    /// into the simulation.
+    stale: bool,
+}
+
+/// Locks the simulation shared with the solver thread.
+#[derive(Clone)]
+pub struct Handle(Arc<Inner>);
+
+struct Inner {
+    shared: Mutex<Shared>,
+    /// Signaled when steps are requested.
+    wake: Condvar,
+    /// Set while the UI is waiting on the lock, so the solver thread backs
+    /// off instead of taking it straight back after each step.
+    waiting: AtomicBool,
+}
+
+impl Runner {
+    /// Starts the solver thread, which lives as long as the app.
+    pub fn new(simulation: Simulation) -> Self {
+        let inner = Arc::new(Inner {
+            shared: Mutex::new(Shared {
+                simulation,
+                solver: Solver::Cpu,
+                gpu_solver: None,
+                error: None,
+                pending: 0,
+                stale: false,
+            }),
+            wake: Condvar::new(),
+            waiting: AtomicBool::new(false),
+        });
+
+        let worker = inner.clone();
+        thread::spawn(move || loop {
+            let mut shared = (worker.wake)
+                .wait_while(worker.shared.lock().unwrap(), |x| x.pending == 0)
+                .unwrap();
+            shared.pending -= 1;
+            shared.tick();
+            drop(shared);
+
+            while worker.waiting.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+        });
+
+        Self {
+            handle: Handle(inner),
+            running: false,
+            steps_per_frame: 1,
+            target_time: None,
+            remesh_every: 1,
+            meshed_step: 0,
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle.clone()
+    }
+
+    /// Requests this frame's steps from the solver thread while running,
+    /// pausing once the target time is reached or if the simulation can't be
+    /// run. Returns whether the iso-surfaces are due to be remeshed.
+    pub fn update(&mut self, shared: &mut Shared) -> bool {
+        // Settings can change anywhere in the UI, so they're checked again
+        // right before any steps are requested
+        self.running &= shared.validate().is_ok();
+
+        let simulation = &shared.simulation;
+        let mut steps = if self.running {
+            self.steps_per_frame
+        } else {
+            0
+        };
+        if let Some(target) = self.target_time {
+            let time = simulation.step as f32 * simulation.config.dt;
+            let remaining = ((target - time) / simulation.config.dt).ceil().max(0.0);
+            steps = steps.min(remaining as usize);
+            self.running &= remaining > 0.0;
+        }
+
+        // Replaces rather than adds to what's left from the last frame, so
+        // a slow solver doesn't build up a backlog
+        shared.pending = steps;
+        self.handle.0.wake.notify_one();
+
+        let since = simulation.step.saturating_sub(self.meshed_step);
+        since >= self.remesh_every || (!self.running && since > 0)
+    }
+}
+
+impl Handle {
+    /// Locks the simulation, waiting for the solver thread to finish its
+    /// current step.
+    pub fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.0.waiting.store(true, Ordering::Release);
+        let shared = self.0.shared.lock().unwrap();
+        self.0.waiting.store(false, Ordering::Release);
+        shared
+    }
+}
+
+impl Shared {
+    /// Advances the simulation by one step. States ticked on the GPU are only
+    /// copied back by [`Shared::sync`].
+    pub fn tick(&mut self) {
+        match &mut self.gpu_solver {
+            Some(gpu_solver) if self.solver == Solver::Gpu => {
+                let result = gpu_solver.tick(&mut self.simulation);
+                self.stale |= result.is_ok();
+                self.report(result);
+            }
+            _ => self.simulation.tick(),
+        }
+    }
+
+    /// Brings the simulation's states up to date with the GPU solver's.
+    pub fn sync(&mut self) {
+        if let (true, Some(gpu_solver)) = (mem::take(&mut self.stale), &self.gpu_solver) {
+            let result = gpu_solver.download(&mut self.simulation);
+            self.report(result);
+        }
+    }
+
+    /// Sends changes made to the simulation on the CPU over to the GPU
+    /// solver, if it's in use. Its states are synced first, so changes to
+    /// the states themselves need to go through [`Shared::reset`].
+    pub fn upload(&mut self) {
+        self.sync();
+        match &mut self.gpu_solver {
+            Some(gpu_solver) if self.solver == Solver::Gpu => {
+                let result = gpu_solver.upload(&self.simulation);
+                self.report(result);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.simulation.reset();
+        self.stale = false;
+        self.error = None;
+        self.upload();
+    }
+
+    /// The GPU solver, if it's the one in use.
+    pub fn active_gpu_solver(&self) -> Option<&GpuSolver> {
+        self.gpu_solver
+            .as_ref()
+            .filter(|_| self.solver == Solver::Gpu)
+    }
+
+    /// Switches solver, creating the GPU solver the first time it's chosen.
+    /// If it can't be created, the current solver is kept.
+    pub fn set_solver(&mut self, gpu: &Gpu, solver: Solver) {
+        self.error = None;
+        self.sync();
+        if solver == Solver::Gpu && self.gpu_solver.is_none() {
+            match GpuSolver::new(gpu, &self.simulation) {
+                Ok(gpu_solver) => self.gpu_solver = Some(gpu_solver),
+                Err(err) => return self.report(Err(err)),
+            }
+        }
+
+        self.solver = solver;
+        self.upload();
+    }
+
+    /// Recreates the GPU solver if it exists, such as after the grid is
+    /// resized. If it can't be, it's dropped in favour of the CPU solver.
+    pub fn recreate_gpu_solver(&mut self, gpu: &Gpu) {
+        if self.gpu_solver.is_none() {
+            return;
+        }
+
+        match GpuSolver::new(gpu, &self.simulation) {
+            Ok(gpu_solver) => self.gpu_solver = Some(gpu_solver),
+            Err(err) => {
+                self.gpu_solver = None;
+                self.solver = Solver::Cpu;
+                self.report(Err(err));
+            }
+        }
+    }
+
+    /// Checks the simulation can be run, by both [`Simulation::validate`] and
+    /// the solver in use, and that the GPU solver hasn't failed.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(err) = &self.error {
+            bail!("The GPU solver failed, reset or change solver to continue: {err:#}");
+        }
+
+        self.simulation.validate()?;
+        self.solver.validate(&self.simulation.config)
+    }
+
+    /// Keeps an error from the GPU solver to show, dropping any steps still
+    /// pending.
+    fn report(&mut self, result: Result<()>) {
+        if let Err(err) = result {
+            self.error = Some(err);
+            self.pending = 0;
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// Trilinearly interpolates the `field` array at a position in cells
+fn sample(pos: vec3f) -> f32 {
+    let max_pos = vec3f(ctx.size - 1u);
+    let clamped = clamp(pos, vec3(0.0), max_pos);
+    let low = min(floor(clamped), max_pos - 1.0);
+    let t = clamped - low;
+
+    var value = 0.0;
+    for (var i = 0u; i < 8u; i++) {
+        let offset = vec3u(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+        let weight = mix(1.0 - t, t, vec3f(offset));
+        value += field[index(vec3u(low) + offset)] * weight.x * weight.y * weight.z;
+    }
+
+    return value;
+}
@@ -0,0 +1,23 @@
+//! WGSL has no way to import from other files, so helpers shared between
+//! shaders are appended to their source before it's compiled.
+
+use std::borrow::Cow;
+
+use compute::export::wgpu::{ShaderModuleDescriptor, ShaderSource};
+
+/// `index`, for fields laid out like on the CPU. Needs a `ctx` uniform with
+/// the grid `size`.
+pub const INDEX: &str = include_str!("index.wgsl");
+/// `sample`, which trilinearly interpolates the `field` array. Needs
+/// [`INDEX`] too.
+pub const SAMPLE: &str = include_str!("sample.wgsl");
+/// `color_map`, which samples the stops in `ctx.color_map`.
+pub const COLOR_MAP: &str = include_str!("color_map.wgsl");
+
+/// Concatenates the sources of a shader and the helpers it uses.
+pub fn compose(label: &'static str, sources: &[&str]) -> ShaderModuleDescriptor<'static> {
+    ShaderModuleDescriptor {
+        label: Some(label),
+        source: ShaderSource::Wgsl(Cow::Owned(sources.join("\n"))),
+    }
+}
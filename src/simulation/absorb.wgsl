@@ -0,0 +1,51 @@
+// The start of the uniform in `tick.wgsl`
+struct Uniform {
+    size: vec3u,
+    step: u32,
+    negative: vec3u,
+    courant: f32
+}
+
+// See `Cell` in `tick.wgsl`
+struct Cell {
+    index: f32,
+    damping: f32,
+    obstacle: u32
+}
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+@group(0) @binding(1) var<storage, read_write> states: array<f32>;
+// Face to apply the condition to, in the order of `Boundaries`
+@group(0) @binding(2) var<uniform> face: u32;
+@group(0) @binding(3) var<storage, read> cells: array<Cell>;
+
+// First order Mur condition on a single face, see `boundary::apply_absorbing`
+@compute
+@workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3u) {
+    let axis = face / 2u;
+    let positive = face % 2u == 1u;
+    let a = (axis + 1u) % 3u;
+    let b = (axis + 2u) % 3u;
+    if id.x >= ctx.size[a] || id.y >= ctx.size[b] {
+        return;
+    }
+
+    let count = ctx.size.x * ctx.size.y * ctx.size.z;
+    let curr = (ctx.step % 3u) * count;
+    let next = ((ctx.step + 1u) % 3u) * count;
+
+    var edge = vec3(0u);
+    edge[a] = id.x;
+    edge[b] = id.y;
+    edge[axis] = select(0u, ctx.size[axis] - 1u, positive);
+    var inner = edge;
+    inner[axis] = select(1u, ctx.size[axis] - 2u, positive);
+
+    let edge_idx = index(edge);
+    let inner_idx = index(inner);
+    // Local Courant number in the medium at the face
+    let courant = ctx.courant / cells[edge_idx].index;
+    let k = (courant - 1.0) / (courant + 1.0);
+    states[next + edge_idx] = states[curr + inner_idx] + k * (states[next + inner_idx] - states[curr + edge_idx]);
+}
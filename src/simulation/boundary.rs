@@ -0,0 +1,131 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use compute::export::nalgebra::Vector3;
+
+/// What happens to waves reaching one face of the grid.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryCondition {
+    /// Field is held at zero past the face, reflecting waves with their sign flipped.
+    Dirichlet,
+    /// Zero normal derivative across the face, reflecting waves unchanged.
+    Neumann,
+    /// Waves leaving the face re-enter from the opposite one.
+    Periodic,
+    /// First order Mur boundary, absorbing waves hitting the face head on.
+    Absorbing,
+}
+
+/// Boundary conditions for every face of the grid, in the order
+/// `-x, +x, -y, +y, -z, +z`.
+pub type Boundaries = [BoundaryCondition; 6];
+
+/// Names of the faces in the same order as [`Boundaries`].
+pub const FACES: [&str; 6] = ["-X", "+X", "-Y", "+Y", "-Z", "+Z"];
+
+pub fn face(axis: usize, positive: bool) -> usize {
+    axis * 2 + positive as usize
+}
+
+/// Finds the cell one step along `axis` from `pos`. Returns `None` if the
+/// neighbour lies past a Dirichlet face and should be treated as zero.
+pub fn neighbor(
+    size: Vector3<usize>,
+    boundaries: &Boundaries,
+    mut pos: Vector3<usize>,
+    axis: usize,
+    positive: bool,
+) -> Option<Vector3<usize>> {
+    let coord = &mut pos[axis];
+    match (positive, *coord) {
+        (true, x) if x + 1 < size[axis] => *coord += 1,
+        (false, x) if x > 0 => *coord -= 1,
+        _ => match boundaries[face(axis, positive)] {
+            BoundaryCondition::Dirichlet => return None,
+            BoundaryCondition::Neumann | BoundaryCondition::Absorbing => {}
+            BoundaryCondition::Periodic => *coord = if positive { 0 } else { size[axis] - 1 },
+        },
+    }
+
+    Some(pos)
+}
+
+/// Overwrites the cells on every absorbing face with the first order Mur
+/// condition, `uₙ₊₁[b] = uₙ[b-1] + (C-1)/(C+1) · (uₙ₊₁[b-1] - uₙ[b])` where
+/// `b-1` is the cell just inside the face and `C` is the local Courant number
+/// at `b`, using the wave speed in its medium.
+pub fn apply_absorbing(
+    size: Vector3<usize>,
+    boundaries: &Boundaries,
+    courant: f32,
+    refractive_index: &[f32],
+    curr: &[f32],
+    next: &mut [f32],
+) {
+    let stride = Vector3::new(size.y * size.z, size.z, 1);
+
+    for (axis, positive) in (0..3).flat_map(|axis| [(axis, false), (axis, true)]) {
+        if boundaries[face(axis, positive)] != BoundaryCondition::Absorbing {
+            continue;
+        }
+
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let edge = if positive { size[axis] - 1 } else { 0 };
+        let inner = if positive { edge - 1 } else { 1 };
+
+        for i in 0..size[a] {
+            for j in 0..size[b] {
+                let base = i * stride[a] + j * stride[b];
+                let (edge, inner) = (base + edge * stride[axis], base + inner * stride[axis]);
+                let courant = courant / refractive_index[edge];
+                let k = (courant - 1.0) / (courant + 1.0);
+                next[edge] = curr[inner] + k * (next[inner] - curr[edge]);
+            }
+        }
+    }
+}
+
+/// Periodic faces only make sense in pairs, so this checks that opposing faces
+/// are either both or neither periodic.
+pub fn validate(boundaries: &Boundaries) -> Result<()> {
+    for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+        let periodic = [false, true]
+            .map(|positive| boundaries[face(axis, positive)] == BoundaryCondition::Periodic);
+        if periodic[0] != periodic[1] {
+            bail!("Both {name} faces must be periodic if either is");
+        }
+    }
+
+    Ok(())
+}
+
+impl BoundaryCondition {
+    pub const ALL: [Self; 4] = [
+        Self::Dirichlet,
+        Self::Neumann,
+        Self::Periodic,
+        Self::Absorbing,
+    ];
+}
+
+impl Display for BoundaryCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Dirichlet => "Dirichlet",
+            Self::Neumann => "Neumann",
+            Self::Periodic => "Periodic",
+            Self::Absorbing => "Absorbing",
+        })
+    }
+}
+
+impl FromStr for BoundaryCondition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("Unknown boundary condition `{s}`"))
+    }
+}
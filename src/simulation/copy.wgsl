@@ -0,0 +1,28 @@
+struct Uniform {
+    size: vec3u,
+    step: u32,
+    energy: u32
+}
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+// The three ring buffered states, see `tick.wgsl`
+@group(0) @binding(1) var<storage, read> states: array<f32>;
+@group(0) @binding(2) var<storage, read> energy: array<f32>;
+@group(0) @binding(3) var<storage, read_write> field: array<f32>;
+
+// Copies the current state or the energy into the field, see `FieldCopy`
+@compute
+@workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) pos: vec3u) {
+    if any(pos >= ctx.size) {
+        return;
+    }
+
+    let idx = index(pos);
+    if ctx.energy != 0u {
+        field[idx] = energy[idx];
+    } else {
+        let count = ctx.size.x * ctx.size.y * ctx.size.z;
+        field[idx] = states[(ctx.step % 3u) * count + idx];
+    }
+}
@@ -0,0 +1,373 @@
+//! Compute shader implementation of [`Simulation::tick`]. The three ring
+//! buffered states are stored back to back in one storage buffer, in the same
+//! order as [`Simulation::states`], as are the PML memory variables, so the
+//! solver fits within the default limit of eight storage buffers per shader
+//! stage.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, ensure, Error, Result};
+use compute::{
+    bindings::{StorageBuffer, UniformBuffer},
+    export::nalgebra::{Vector2, Vector3},
+    gpu::Gpu,
+    pipeline::compute::ComputePipeline,
+};
+use encase::ShaderType;
+
+use super::{
+    boundary::{face, BoundaryCondition},
+    pml::PmlFields,
+    Config, Simulation,
+};
+use crate::shader::{self, INDEX};
+
+/// `absorption` and `face_neighbor`, for the shaders updating the PML memory
+/// variables.
+const PML: &str = include_str!("pml.wgsl");
+
+/// The most sources the GPU solver supports, limited by the size of the
+/// source array in `tick.wgsl`.
+pub const MAX_SOURCES: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Solver {
+    Cpu,
+    Gpu,
+}
+
+/// Runs the simulation on the GPU. Its states are only copied back into the
+/// [`Simulation`] by [`GpuSolver::download`], and any changes made to the
+/// simulation on the CPU need to be sent over with [`GpuSolver::upload`].
+pub struct GpuSolver {
+    tick: ComputePipeline,
+    /// Advances the PML's `ψ`, which the tick needs for every cell first.
+    psi: ComputePipeline,
+    /// One per face, each bound to its own uniform holding the face index.
+    absorb: Vec<ComputePipeline>,
+    uniform: UniformBuffer<TickUniform>,
+    /// Only kept alive for the absorb pipelines' bindings.
+    _faces: Vec<UniformBuffer<u32>>,
+    states: StorageBuffer<Vec<f32>>,
+    energy: StorageBuffer<Vec<f32>>,
+    cells: StorageBuffer<Vec<Cell>>,
+    pml: StorageBuffer<Vec<f32>>,
+    sigma: StorageBuffer<Vec<Vector2<f32>>>,
+    size: Vector3<usize>,
+
+    /// Whether the PML buffer holds the memory variables, rather than being
+    /// stale as the PML isn't in use.
+    pml_active: bool,
+}
+
+/// Copies the current state or the energy from a [`GpuSolver`] into a field
+/// buffer on the GPU, for whatever reads the field there to use without a
+/// round trip through the CPU. It needs to be recreated along with the
+/// solver or the field.
+pub struct FieldCopy {
+    pipeline: ComputePipeline,
+    uniform: UniformBuffer<CopyUniform>,
+    size: Vector3<usize>,
+}
+
+#[derive(ShaderType, Default)]
+struct TickUniform {
+    size: Vector3<u32>,
+    step: u32,
+    negative: Vector3<u32>,
+    courant: f32,
+    positive: Vector3<u32>,
+    damping: f32,
+    dt: f32,
+    pml: u32,
+    source_count: u32,
+    sources: [GpuSource; MAX_SOURCES],
+}
+
+#[derive(ShaderType, Default, Clone, Copy)]
+struct GpuSource {
+    position: Vector3<f32>,
+    width: f32,
+    /// The oscillator's value on the current step.
+    value: f32,
+}
+
+#[derive(ShaderType, Default)]
+struct CopyUniform {
+    size: Vector3<u32>,
+    step: u32,
+    energy: u32,
+}
+
+#[derive(ShaderType)]
+struct Cell {
+    index: f32,
+    damping: f32,
+    obstacle: u32,
+}
+
+impl GpuSolver {
+    pub fn new(gpu: &Gpu, simulation: &Simulation) -> Result<Self> {
+        let size = simulation.config.size;
+        let cells = size.iter().product::<usize>();
+
+        let uniform = gpu.create_uniform(&TickUniform::default())?;
+        let faces = (0..6)
+            .map(|face| gpu.create_uniform(&face))
+            .collect::<Result<Vec<_>>>()?;
+        let states = gpu.create_storage(&vec![0.0; cells * 3])?;
+        let energy = gpu.create_storage(&vec![0.0; cells])?;
+        let cells_buffer = gpu.create_storage(&cell_data(simulation))?;
+        let pml = gpu.create_storage(&vec![0.0; cells * 6])?;
+        let sigma = gpu.create_storage(&vec![Vector2::zeros(); size.sum() * 2])?;
+
+        let tick = gpu
+            .compute_pipeline(shader::compose(
+                "tick.wgsl",
+                &[include_str!("tick.wgsl"), PML, INDEX],
+            ))
+            .bind(&uniform)
+            .bind(&states)
+            .bind(&energy)
+            .bind(&cells_buffer)
+            .bind(&pml)
+            .bind(&sigma)
+            .finish();
+        let psi = gpu
+            .compute_pipeline(shader::compose(
+                "psi.wgsl",
+                &[include_str!("psi.wgsl"), PML, INDEX],
+            ))
+            .bind(&uniform)
+            .bind(&states)
+            .bind(&cells_buffer)
+            .bind(&pml)
+            .bind(&sigma)
+            .finish();
+        let absorb = (faces.iter())
+            .map(|face| {
+                gpu.compute_pipeline(shader::compose(
+                    "absorb.wgsl",
+                    &[include_str!("absorb.wgsl"), INDEX],
+                ))
+                .bind(&uniform)
+                .bind(&states)
+                .bind(face)
+                .bind(&cells_buffer)
+                .finish()
+            })
+            .collect();
+
+        let mut solver = Self {
+            tick,
+            psi,
+            absorb,
+            uniform,
+            _faces: faces,
+            states,
+            energy,
+            cells: cells_buffer,
+            pml,
+            sigma,
+            size,
+
+            pml_active: false,
+        };
+        solver.upload(simulation)?;
+        Ok(solver)
+    }
+
+    /// Replaces everything on the GPU with the simulation's current states,
+    /// media and obstacles.
+    pub fn upload(&mut self, simulation: &Simulation) -> Result<()> {
+        self.states.upload(&simulation.states.concat())?;
+        self.energy.upload(&simulation.energy)?;
+        self.cells.upload(&cell_data(simulation))?;
+
+        self.pml_active = simulation.pml.is_some();
+        if let Some(pml) = &simulation.pml {
+            self.pml
+                .upload(&[&pml.psi[..], &pml.zeta[..]].concat().concat())?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the states, energy and PML memory variables back into the
+    /// simulation.
+    pub fn download(&self, simulation: &mut Simulation) -> Result<()> {
+        let cells = self.size.iter().product::<usize>();
+        let states = self.states.download()?;
+        let mut states = states.chunks_exact(cells).map(<[f32]>::to_vec);
+        simulation.states = [(); 3].map(|_| states.next().unwrap());
+        simulation.energy = self.energy.download()?;
+
+        simulation.pml = None;
+        if self.pml_active {
+            let pml = self.pml.download()?;
+            let mut axes = pml.chunks_exact(cells).map(<[f32]>::to_vec);
+            simulation.pml = Some(PmlFields {
+                psi: [(); 3].map(|_| axes.next().unwrap()),
+                zeta: [(); 3].map(|_| axes.next().unwrap()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Advances the simulation by one step, the same way as
+    /// [`Simulation::tick`].
+    pub fn tick(&mut self, simulation: &mut Simulation) -> Result<()> {
+        let config = &simulation.config;
+        let size = self.size;
+        ensure!(
+            config.size == size,
+            "Grid was resized, the GPU solver must be recreated"
+        );
+
+        match config.pml {
+            Some(pml_config) => {
+                // Starts with no history, like `PmlFields::new`
+                if !self.pml_active {
+                    let cells = size.iter().product::<usize>();
+                    self.pml.upload(&vec![0.0; cells * 6])?;
+                    self.pml_active = true;
+                }
+
+                let sigma = [0, 1, 2].map(|i| pml_config.profile(size[i], config));
+                self.sigma.upload(&sigma.concat())?;
+            }
+            None => self.pml_active = false,
+        }
+
+        let conditions =
+            |positive| Vector3::from_fn(|axis, _| config.boundaries[face(axis, positive)] as u32);
+        let mut sources = [GpuSource::default(); MAX_SOURCES];
+        for (slot, source) in sources.iter_mut().zip(&config.sources) {
+            *slot = GpuSource {
+                position: source.position,
+                width: source.width,
+                value: source.oscillator(simulation.step),
+            };
+        }
+
+        self.uniform.upload(&TickUniform {
+            size: size.map(|x| x as u32),
+            step: simulation.step as u32,
+            negative: conditions(false),
+            courant: config.courant(),
+            positive: conditions(true),
+            damping: config.damping,
+            dt: config.dt,
+            pml: self.pml_active as u32,
+            source_count: config.sources.len().min(MAX_SOURCES) as u32,
+            sources,
+        })?;
+        // The uniform is shared by this step's dispatches and uploaded again
+        // next step, which relies on every dispatch being submitted straight
+        // away, so a later upload can't land before it runs
+        let workgroups = size.map(|x| x.div_ceil(4) as u32);
+        if self.pml_active {
+            self.psi.dispatch(workgroups);
+        }
+        self.tick.dispatch(workgroups);
+
+        // Faces are done one at a time, in the same order as on the CPU, as
+        // the cells along their edges are shared.
+        for (axis, positive) in (0..3).flat_map(|axis| [(axis, false), (axis, true)]) {
+            let face = face(axis, positive);
+            if config.boundaries[face] != BoundaryCondition::Absorbing {
+                continue;
+            }
+
+            let plane = Vector2::new(size[(axis + 1) % 3], size[(axis + 2) % 3]);
+            self.absorb[face].dispatch(plane.map(|x| x.div_ceil(8) as u32).push(1));
+        }
+
+        simulation.step += 1;
+        Ok(())
+    }
+}
+
+impl FieldCopy {
+    pub fn new(gpu: &Gpu, solver: &GpuSolver, field: &StorageBuffer<Vec<f32>>) -> Result<Self> {
+        let uniform = gpu.create_uniform(&CopyUniform::default())?;
+        let pipeline = gpu
+            .compute_pipeline(shader::compose(
+                "copy.wgsl",
+                &[include_str!("copy.wgsl"), INDEX],
+            ))
+            .bind(&uniform)
+            .bind(&solver.states)
+            .bind(&solver.energy)
+            .bind(field)
+            .finish();
+
+        Ok(Self {
+            pipeline,
+            uniform,
+            size: solver.size,
+        })
+    }
+
+    /// Copies the solver's current state, or its energy if `energy` is set,
+    /// as of the simulation's step.
+    pub fn copy(&mut self, simulation: &Simulation, energy: bool) -> Result<()> {
+        self.uniform.upload(&CopyUniform {
+            size: self.size.map(|x| x as u32),
+            step: simulation.step as u32,
+            energy: energy as u32,
+        })?;
+        self.pipeline
+            .dispatch(self.size.map(|x| x.div_ceil(4) as u32));
+        Ok(())
+    }
+}
+
+impl Solver {
+    pub const ALL: [Self; 2] = [Self::Cpu, Self::Gpu];
+
+    /// Checks the limits the solver has on top of [`Simulation::validate`].
+    pub fn validate(self, config: &Config) -> Result<()> {
+        if self == Self::Gpu {
+            ensure!(
+                config.sources.len() <= MAX_SOURCES,
+                "The GPU solver supports at most {MAX_SOURCES} sources"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn cell_data(simulation: &Simulation) -> Vec<Cell> {
+    (simulation.refractive_index.iter())
+        .zip(&simulation.damping)
+        .zip(&simulation.obstacles)
+        .map(|((&index, &damping), &obstacle)| Cell {
+            index,
+            damping,
+            obstacle: obstacle as u32,
+        })
+        .collect()
+}
+
+impl Display for Solver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Cpu => "CPU",
+            Self::Gpu => "GPU",
+        })
+    }
+}
+
+impl FromStr for Solver {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("Unknown solver `{s}`"))
+    }
+}
@@ -0,0 +1,39 @@
+use std::{fs, path::Path};
+
+use anyhow::{ensure, Context, Result};
+
+use super::shape::Shape;
+
+/// A region of the grid with its own refractive index. The local wave speed
+/// is the global [`Config::v`](super::Config::v) divided by the index.
+#[derive(Clone, Copy)]
+pub struct Medium {
+    pub shape: Shape,
+    pub index: f32,
+    /// Extra damping in 1/s, on top of [`Config::damping`](super::Config::damping).
+    pub damping: f32,
+}
+
+/// Loads a refractive index for every cell from a file of raw little-endian
+/// `f32`s, laid out the same way as the simulation states.
+pub fn load_raw(path: &Path, cells: usize) -> Result<Vec<f32>> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    ensure!(
+        bytes.len() == cells * 4,
+        "Expected {} bytes in `{}` but found {}",
+        cells * 4,
+        path.display(),
+        bytes.len()
+    );
+
+    let field = bytes
+        .chunks_exact(4)
+        .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect::<Vec<_>>();
+    ensure!(
+        field.iter().all(|&x| x > 0.0),
+        "Refractive indices must be positive"
+    );
+
+    Ok(field)
+}
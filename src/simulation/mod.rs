@@ -0,0 +1,456 @@
+use std::{mem, path::PathBuf};
+
+use anyhow::{ensure, Result};
+use compute::export::nalgebra::Vector3;
+use itertools::Itertools;
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+
+use crate::{
+    marching_cubes::{Mesher, Normals},
+    vertex::Vertex,
+};
+use boundary::{Boundaries, BoundaryCondition};
+use medium::Medium;
+use pml::{Pml, PmlFields};
+use scalar::Scalar;
+use shape::Shape;
+
+pub mod boundary;
+pub mod gpu;
+pub mod medium;
+pub mod pml;
+pub mod scalar;
+pub mod shape;
+
+pub struct Simulation {
+    pub states: [Vec<f32>; 3],
+    pub energy: Vec<f32>,
+    pub step: usize,
+    /// Allocated on the first tick with [`Config::pml`] set.
+    pub pml: Option<PmlFields>,
+    /// Built from [`Config::media`] by [`Simulation::rebuild_media`].
+    pub refractive_index: Vec<f32>,
+    /// Per-cell damping in 1/s, added to [`Config::damping`].
+    pub damping: Vec<f32>,
+    /// Cells filled by [`Config::obstacles`], which are held at zero.
+    pub obstacles: Vec<bool>,
+
+    pub config: Config,
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub size: Vector3<usize>,
+    pub v: f32,
+    pub dx: f32,
+    pub dt: f32,
+    /// Damping coefficient `γ` in 1/s applied to every cell.
+    pub damping: f32,
+    pub sources: Vec<Source>,
+    pub boundaries: Boundaries,
+    pub pml: Option<Pml>,
+    /// Painted in order on top of [`Config::media_file`], or a uniform index
+    /// of one if there is no file.
+    pub media: Vec<Medium>,
+    pub media_file: Option<PathBuf>,
+    pub obstacles: Vec<Shape>,
+}
+
+/// A point oscillator, smeared out over a few cells with an exponential falloff.
+#[derive(Clone, Copy)]
+pub struct Source {
+    /// Center of the source in cells.
+    pub position: Vector3<f32>,
+    pub amplitude: f32,
+    /// Angular frequency in radians per tick.
+    pub frequency: f32,
+    pub phase: f32,
+    /// Distance in cells over which the source decays by a factor of e.
+    pub width: f32,
+}
+
+impl Simulation {
+    pub fn new(config: Config) -> Result<Self> {
+        let cells = config.size.iter().product();
+        let mut simulation = Self {
+            states: [(); 3].map(|_| vec![0.0; cells]),
+            energy: vec![0.0; cells],
+            step: 0,
+            pml: None,
+            refractive_index: vec![1.0; cells],
+            damping: vec![0.0; cells],
+            obstacles: vec![false; cells],
+            config,
+        };
+        simulation.rebuild_media()?;
+        simulation.rebuild_obstacles();
+        Ok(simulation)
+    }
+
+    pub fn rebuild_obstacles(&mut self) {
+        let size = self.config.size;
+        self.obstacles = vec![false; size.iter().product()];
+        for obstacle in &self.config.obstacles {
+            obstacle.fill(&mut self.obstacles, size, true);
+        }
+    }
+
+    pub fn rebuild_media(&mut self) -> Result<()> {
+        let size = self.config.size;
+        let cells = size.iter().product();
+        self.refractive_index = match &self.config.media_file {
+            Some(path) => medium::load_raw(path, cells)?,
+            None => vec![1.0; cells],
+        };
+        self.damping = vec![0.0; cells];
+
+        for medium in &self.config.media {
+            ensure!(medium.index > 0.0, "Refractive indices must be positive");
+            ensure!(medium.damping >= 0.0, "Damping must not be negative");
+            (medium.shape).fill(&mut self.refractive_index, size, medium.index);
+            (medium.shape).fill(&mut self.damping, size, medium.damping);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the config along with the stability of the fastest cell, which
+    /// is the one with the smallest refractive index.
+    pub fn validate(&self) -> Result<()> {
+        self.config.validate()?;
+
+        let min_index = self
+            .refractive_index
+            .iter()
+            .copied()
+            .fold(f32::MAX, f32::min);
+        let courant = self.config.courant() / min_index;
+        ensure!(
+            courant <= Config::MAX_COURANT,
+            "Unstable time step: c·dt/dx = {courant:.3} in the fastest medium exceeds 1/√3 ≈ {:.3}",
+            Config::MAX_COURANT
+        );
+        Ok(())
+    }
+
+    /// Reallocates every field for a new grid size, either resampling the
+    /// states, energy, PML components and source positions into it or
+    /// starting over at rest. Otherwise sources keep their positions in cells
+    /// and any left outside the grid are removed, so compare
+    /// [`Config::sources`] before and after to warn about them. Media and
+    /// obstacles keep their positions in cells and are rebuilt at the new
+    /// size. If the media can't be rebuilt, such as when the index file no
+    /// longer matches, they are left uniform.
+    pub fn resize(&mut self, size: Vector3<usize>, resample: bool) -> Result<()> {
+        ensure!(
+            size.iter().all(|&x| x >= 2),
+            "Grid must be at least two cells along every axis"
+        );
+
+        let old = mem::replace(&mut self.config.size, size);
+        let cells = size.iter().product();
+        if resample {
+            self.states = self.states.each_ref().map(|x| resample_field(x, old, size));
+            self.energy = resample_field(&self.energy, old, size);
+            if let Some(pml) = &mut self.pml {
+                for field in pml.psi.iter_mut().chain(&mut pml.zeta) {
+                    *field = resample_field(field, old, size);
+                }
+            }
+
+            // Line the corner cells up the same way the fields are resampled
+            let scale = old.zip_map(&size, |from, to| (to - 1) as f32 / (from - 1) as f32);
+            for source in &mut self.config.sources {
+                source.position.component_mul_assign(&scale);
+            }
+        } else {
+            self.reset();
+            self.energy = vec![0.0; cells];
+            self.config.sources.retain(|x| x.inside(size));
+        }
+
+        self.rebuild_obstacles();
+        let media = self.rebuild_media();
+        if media.is_err() {
+            self.refractive_index = vec![1.0; cells];
+            self.damping = vec![0.0; cells];
+        }
+        media
+    }
+
+    pub fn reset(&mut self) {
+        let cells = self.config.size.iter().product();
+        self.states = [(); 3].map(|_| vec![0.0; cells]);
+        self.step = 0;
+        self.pml = None;
+    }
+
+    /// ```plain
+    /// ∂²u     ∂u        ∂²u   ∂²u   ∂²u
+    /// --- + γ -- = c² ( --- + --- + --- )
+    /// ∂t²     ∂t        ∂x²   ∂y²   ∂z²
+    /// ```
+    ///
+    /// Discretized with second order central differences in space and time,
+    /// which leaves the squared Courant number `(c·dt/dx)²` in front of the
+    /// seven point Laplacian, where `c` is the local wave speed `v/n`. Cells
+    /// in and next to the PML have the second difference along each axis
+    /// stretched by [`PmlSlab::stretch`](pml::PmlSlab::stretch), and cells
+    /// inside obstacles are held at zero so waves reflect off of them.
+    pub fn tick(&mut self) {
+        let Simulation {
+            states,
+            energy,
+            step,
+            pml,
+            refractive_index,
+            damping,
+            obstacles,
+            config,
+        } = self;
+
+        let size = config.size;
+        let boundaries = config.boundaries;
+        let courant = config.courant();
+        let c = courant.powi(2);
+        let (global_damping, dt) = (config.damping, config.dt);
+        let step = *step;
+
+        let (prev, curr, next) = rotate_states(states, step);
+        let (sigma, thickness) = match config.pml {
+            Some(pml_config) => {
+                let sigma = [0, 1, 2].map(|i| pml_config.profile(size[i], config));
+                let fields = pml.get_or_insert_with(|| PmlFields::new(curr.len()));
+                fields.update_psi(size, &boundaries, &sigma, refractive_index, curr);
+                (sigma, pml_config.thickness)
+            }
+            None => {
+                *pml = None;
+                Default::default()
+            }
+        };
+
+        let slab = size.y * size.z;
+        let stride = Vector3::new(slab, size.z, 1);
+        let index = |pos: Vector3<usize>| pos.dot(&stride);
+        let neighbor =
+            |pos, axis, positive| boundary::neighbor(size, &boundaries, pos, axis, positive);
+
+        let sources = (config.sources.iter())
+            .map(|x| (x, x.oscillator(step)))
+            .collect::<Vec<_>>();
+        let pml_slabs = match pml {
+            Some(pml) => pml.slabs(slab).map(Some).collect::<Vec<_>>(),
+            None => (0..size.x).map(|_| None).collect(),
+        };
+
+        (next.par_chunks_mut(slab))
+            .zip(energy.par_chunks_mut(slab))
+            .zip(pml_slabs)
+            .enumerate()
+            .for_each(|(x, ((next, energy), mut pml))| {
+                for (y, z) in (0..size.y).cartesian_product(0..size.z) {
+                    let pos = Vector3::new(x, y, z);
+                    let (local, idx) = (y * size.z + z, x * slab + y * size.z + z);
+                    if obstacles[idx] {
+                        (next[local], energy[local]) = (0.0, 0.0);
+                        continue;
+                    }
+
+                    // Cells away from the faces can skip resolving boundaries
+                    let interior = (0..3).all(|i| pos[i] > 0 && pos[i] + 1 < size[i]);
+                    let mut laplacian = Vector3::from_fn(|axis, _| {
+                        let [a, b] = if interior {
+                            [curr[idx + stride[axis]], curr[idx - stride[axis]]]
+                        } else {
+                            [true, false].map(|positive| {
+                                neighbor(pos, axis, positive).map_or(0.0, |x| curr[index(x)])
+                            })
+                        };
+                        a + b - 2.0 * curr[idx]
+                    });
+
+                    // Only cells with a face in the layer have memory variables
+                    let in_layer =
+                        (0..3).any(|i| pos[i] <= thickness || pos[i] + thickness >= size[i] - 1);
+                    if let Some(pml) = pml.as_mut().filter(|_| in_layer) {
+                        let sigma = [0, 1, 2]
+                            .map(|axis| sigma[axis][pos[axis] * 2] / refractive_index[idx]);
+                        let below = [0, 1, 2].map(|axis| {
+                            pml::face_neighbor(size, &boundaries, pos, axis, false).map(index)
+                        });
+                        laplacian = pml.stretch(local, idx, below, sigma, laplacian);
+                    }
+
+                    let laplacian = laplacian.sum() * c / refractive_index[idx].powi(2);
+                    let loss = (global_damping + damping[idx]) * dt / 2.0;
+                    let u = (laplacian - (1.0 - loss) * prev[idx] + 2.0 * curr[idx]) / (1.0 + loss)
+                        + (sources.iter())
+                            .map(|(source, value)| source.falloff(pos.map(|x| x as f32)) * value)
+                            .sum::<f32>();
+
+                    let nd = step as f32 + 1.0;
+                    energy[local] = energy[local] * (step as f32 / nd) + u.powi(2) / nd;
+                    next[local] = u;
+                }
+            });
+
+        boundary::apply_absorbing(size, &boundaries, courant, refractive_index, curr, next);
+        self.step += 1;
+    }
+
+    pub fn state(&self) -> &Vec<f32> {
+        &self.states[self.step % 3]
+    }
+
+    pub fn triangluate(
+        &self,
+        mesher: Mesher,
+        iso_levels: &[f32],
+        normals: Normals,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        mesher.mesh_levels(self.state(), self.config.size, iso_levels, normals)
+    }
+
+    pub fn triangluate_energy(
+        &self,
+        mesher: Mesher,
+        iso_levels: &[f32],
+        normals: Normals,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        mesher.mesh_levels(&self.energy, self.config.size, iso_levels, normals)
+    }
+
+    /// Sets the scalar of every vertex to the value of `scalar` at its position.
+    pub fn vertex_scalars(&self, scalar: Scalar, vertices: &mut [Vertex]) {
+        (vertices.par_iter_mut()).for_each(|x| x.scalar = scalar.evaluate(self, x.position.xyz()));
+    }
+
+    pub fn triangluate_obstacles(
+        &self,
+        mesher: Mesher,
+        normals: Normals,
+    ) -> (Vec<Vertex>, Vec<u32>) {
+        let field = (self.obstacles.iter())
+            .map(|&x| x as u8 as f32)
+            .collect::<Vec<_>>();
+        mesher.mesh(&field, self.config.size, 0.5, normals)
+    }
+}
+
+/// Trilinearly interpolates a field onto a grid of a different size, with the
+/// corner cells of both grids lined up.
+fn resample_field(field: &[f32], from: Vector3<usize>, to: Vector3<usize>) -> Vec<f32> {
+    let scale = from.zip_map(&to, |from, to| (from - 1) as f32 / (to - 1) as f32);
+    (0..to.x)
+        .cartesian_product(0..to.y)
+        .cartesian_product(0..to.z)
+        .map(|((x, y), z)| {
+            let pos = Vector3::new(x, y, z).map(|x| x as f32);
+            scalar::sample(field, from, pos.component_mul(&scale))
+        })
+        .collect()
+}
+
+/// Splits the ring buffer of states into the previous, current and next
+/// states for the given step.
+fn rotate_states(states: &mut [Vec<f32>; 3], step: usize) -> (&[f32], &[f32], &mut [f32]) {
+    let [a, b, c] = states;
+    match step % 3 {
+        0 => (c, a, b),
+        1 => (a, b, c),
+        _ => (b, c, a),
+    }
+}
+
+impl Config {
+    /// The largest Courant number for which the 3D leapfrog scheme is stable.
+    pub const MAX_COURANT: f32 = 0.577_350_26; // 1/√3
+
+    /// Creates a config with the default pair of sources, erroring if the
+    /// time step violates the CFL condition.
+    pub fn new(size: Vector3<usize>, v: f32, dx: f32, dt: f32) -> Result<Self> {
+        let center = size.map(|x| x as f32) / 2.0;
+        let offset = Vector3::x() * 15.0;
+
+        let config = Config {
+            size,
+            v,
+            dx,
+            dt,
+            damping: 0.0,
+            sources: vec![Source::new(center + offset), Source::new(center - offset)],
+            boundaries: [BoundaryCondition::Dirichlet; 6],
+            pml: None,
+            media: Vec::new(),
+            media_file: None,
+            obstacles: Vec::new(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// `c·dt/dx`
+    pub fn courant(&self) -> f32 {
+        self.v * self.dt / self.dx
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.size.iter().all(|&x| x >= 2),
+            "Grid must be at least two cells along every axis"
+        );
+        ensure!(
+            self.v > 0.0 && self.dx > 0.0 && self.dt > 0.0,
+            "Wave speed, dx and dt must be positive"
+        );
+        ensure!(self.damping >= 0.0, "Damping must not be negative");
+        boundary::validate(&self.boundaries)?;
+        if let Some(pml) = &self.pml {
+            pml.validate(self)?;
+        }
+        ensure!(
+            self.courant() <= Self::MAX_COURANT,
+            "Unstable time step: c·dt/dx = {:.3} exceeds 1/√3 ≈ {:.3}",
+            self.courant(),
+            Self::MAX_COURANT
+        );
+        Ok(())
+    }
+}
+
+impl Source {
+    pub fn new(position: Vector3<f32>) -> Self {
+        Self {
+            position,
+            amplitude: 1.0,
+            frequency: 0.1,
+            phase: 0.0,
+            width: 1.0,
+        }
+    }
+
+    /// The source's value at its center on the given step.
+    pub fn oscillator(&self, step: usize) -> f32 {
+        self.amplitude * (step as f32 * self.frequency + self.phase).cos()
+    }
+
+    /// Whether the center of the source lies within a grid of `size` cells.
+    pub fn inside(&self, size: Vector3<usize>) -> bool {
+        (self.position.iter().zip(&size)).all(|(&x, &size)| (0.0..=(size - 1) as f32).contains(&x))
+    }
+
+    /// How much of the oscillator reaches `pos`, from one at the center.
+    pub fn falloff(&self, pos: Vector3<f32>) -> f32 {
+        (-(self.position - pos).magnitude() / self.width).exp()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new(Vector3::repeat(100), 1.0, 0.1, 0.01).unwrap()
+    }
+}
@@ -0,0 +1,286 @@
+//! Convolutional perfectly matched layer (CPML) for the second order wave
+//! equation, following Pasalic and McGarry (2010). Inside the layer every
+//! derivative along an axis is stretched, `∂ᵢ → (1/sᵢ) ∂ᵢ` with
+//! `sᵢ = 1 + σᵢ/(αᵢ + iω)`, which expands to
+//!
+//! ```plain
+//! (1/sᵢ) ∂ᵢ((1/sᵢ) ∂ᵢu) = ∂²u/∂xᵢ² + ∂ψᵢ/∂xᵢ + ζᵢ
+//! ```
+//!
+//! The memory variables are recursive convolutions of the field, `ψᵢ` on the
+//! faces between cells and `ζᵢ` on the cells, with `bᵢ = exp(-(σᵢ + αᵢ)·dt)`
+//! and `aᵢ = σᵢ/(σᵢ + αᵢ)·(bᵢ - 1)`:
+//!
+//! ```plain
+//! ψᵢ ← bᵢ ψᵢ + aᵢ ∂u/∂xᵢ
+//! ζᵢ ← bᵢ ζᵢ + aᵢ (∂²u/∂xᵢ² + ∂ψᵢ/∂xᵢ)
+//! ```
+//!
+//! Because `∂ψᵢ/∂xᵢ` carries the change in `σᵢ` across the layer, waves enter
+//! it without reflecting off of the ramp in absorption and then decay before
+//! reaching the grid edge. The frequency shift `αᵢ` keeps the stretching
+//! finite as `ω → 0`, so slowly varying fields drain out of the layer instead
+//! of being held in it. Outside of the layer both memory variables stay
+//! zero and the update is the plain wave equation.
+
+use anyhow::{ensure, Result};
+use compute::export::nalgebra::{Vector2, Vector3};
+use itertools::Itertools;
+use rayon::{
+    iter::{IndexedParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+
+use super::{
+    boundary::{self, Boundaries},
+    Config,
+};
+
+#[derive(Clone, Copy)]
+pub struct Pml {
+    /// Width of the absorbing layer in cells.
+    pub thickness: usize,
+    /// Theoretical amplitude reflected by the layer at normal incidence.
+    pub reflection: f32,
+}
+
+/// Memory variables for every cell. Only the values in and next to the layer
+/// ever become non-zero.
+pub struct PmlFields {
+    /// `ψ` along each axis, on the face between each cell and the next cell
+    /// along the axis.
+    pub psi: [Vec<f32>; 3],
+    /// `ζ` along each axis, on each cell.
+    pub zeta: [Vec<f32>; 3],
+}
+
+/// The `ζ` of a single x slab, indexed relative to the slab start, along with
+/// all of `ψ` as the cells on the slab's lower face need the slab below's.
+pub struct PmlSlab<'a> {
+    psi: &'a [Vec<f32>; 3],
+    zeta: [&'a mut [f32]; 3],
+}
+
+impl Pml {
+    /// Damping at the outer edge of the layer, in 1/s, for a quadratic
+    /// absorption profile in a medium with wave speed `v`. The profile is
+    /// computed for the vacuum speed and scaled by `1/n` in each cell, as
+    /// the damping needed is proportional to the local speed `v/n`.
+    pub fn sigma_max(&self, v: f32, dx: f32) -> f32 {
+        let width = self.thickness as f32 * dx;
+        3.0 * v * (1.0 / self.reflection).ln() / (2.0 * width)
+    }
+
+    /// Frequency shift at the inner edge of the layer, in 1/s, falling to
+    /// zero at the outer edge. A fraction of the inverse time a wave takes to
+    /// cross the layer is enough to let anything trapped in it relax while
+    /// barely affecting how the waves passing through are absorbed.
+    pub fn alpha_max(&self, v: f32, dx: f32) -> f32 {
+        0.3 * v / (self.thickness as f32 * dx)
+    }
+
+    /// Absorption and frequency shift per tick (`σ·dt` and `α·dt`) along an
+    /// axis of length `n`, at each cell followed by the face between it and
+    /// the next cell.
+    pub fn profile(&self, n: usize, config: &Config) -> Vec<Vector2<f32>> {
+        let thickness = self.thickness as f32;
+        let sigma_max = self.sigma_max(config.v, config.dx) * config.dt;
+        let alpha_max = self.alpha_max(config.v, config.dx) * config.dt;
+        (0..n * 2)
+            .map(|i| {
+                let pos = i as f32 / 2.0;
+                let depth = (thickness - pos)
+                    .max(pos - (n - 1 - self.thickness) as f32)
+                    .clamp(0.0, thickness)
+                    / thickness;
+                match depth {
+                    0.0 => Vector2::zeros(),
+                    _ => Vector2::new(sigma_max * depth.powi(2), alpha_max * (1.0 - depth)),
+                }
+            })
+            .collect()
+    }
+
+    pub fn validate(&self, config: &Config) -> Result<()> {
+        ensure!(self.thickness > 0, "PML must be at least one cell thick");
+        ensure!(
+            config.size.iter().all(|&x| x > 2 * self.thickness),
+            "PML is too thick for the grid"
+        );
+        ensure!(
+            self.reflection > 0.0 && self.reflection < 1.0,
+            "PML reflection must be between zero and one"
+        );
+        Ok(())
+    }
+}
+
+impl Default for Pml {
+    fn default() -> Self {
+        Self {
+            thickness: 10,
+            reflection: 1e-6,
+        }
+    }
+}
+
+impl PmlFields {
+    /// Starts with no history, so the layer picks up the field already
+    /// inside it when it's enabled part way through a run.
+    pub fn new(cells: usize) -> Self {
+        Self {
+            psi: [(); 3].map(|_| vec![0.0; cells]),
+            zeta: [(); 3].map(|_| vec![0.0; cells]),
+        }
+    }
+
+    /// Advances `ψ` on every face with the layer's absorption from the
+    /// current state, which has to happen for the whole grid before any
+    /// cell's `∂ψ/∂x` can be taken.
+    pub fn update_psi(
+        &mut self,
+        size: Vector3<usize>,
+        boundaries: &Boundaries,
+        sigma: &[Vec<Vector2<f32>>; 3],
+        refractive_index: &[f32],
+        curr: &[f32],
+    ) {
+        let slab = size.y * size.z;
+        let stride = Vector3::new(slab, size.z, 1);
+        for (axis, psi) in self.psi.iter_mut().enumerate() {
+            (psi.par_chunks_mut(slab)).enumerate().for_each(|(x, psi)| {
+                for (y, z) in (0..size.y).cartesian_product(0..size.z) {
+                    let pos = Vector3::new(x, y, z);
+                    let (local, idx) = (y * size.z + z, pos.dot(&stride));
+
+                    // Scaled by the local wave speed, see `Pml::sigma_max`
+                    let s = sigma[axis][pos[axis] * 2 + 1] / refractive_index[idx];
+                    psi[local] = match face_neighbor(size, boundaries, pos, axis, true) {
+                        Some(next) if s != Vector2::zeros() => {
+                            let (b, a) = coefficients(s);
+                            let gradient = curr[next.dot(&stride)] - curr[idx];
+                            b * psi[local] + a * gradient
+                        }
+                        _ => 0.0,
+                    };
+                }
+            });
+        }
+    }
+
+    /// Splits `ζ` into chunks of `len` cells, one per x slab of the grid, so
+    /// slabs can be updated in parallel.
+    pub fn slabs(&mut self, len: usize) -> impl Iterator<Item = PmlSlab<'_>> {
+        let Self { psi, zeta } = self;
+        let psi = &*psi;
+        let [z0, z1, z2] = zeta;
+        (z0.chunks_mut(len)
+            .zip(z1.chunks_mut(len))
+            .zip(z2.chunks_mut(len)))
+        .map(move |((z0, z1), z2)| PmlSlab {
+            psi,
+            zeta: [z0, z1, z2],
+        })
+    }
+}
+
+/// The cell sharing the face past `pos` along an axis, which is only another
+/// cell inside the grid or wrapped around a periodic boundary. There are no
+/// memory variables for the faces on the other grid edges.
+pub fn face_neighbor(
+    size: Vector3<usize>,
+    boundaries: &Boundaries,
+    pos: Vector3<usize>,
+    axis: usize,
+    positive: bool,
+) -> Option<Vector3<usize>> {
+    boundary::neighbor(size, boundaries, pos, axis, positive).filter(|&x| x != pos)
+}
+
+/// The decay `b` and gain `a` of the recursive convolution for a cell or face
+/// with the given absorption and frequency shift per tick.
+fn coefficients(absorption: Vector2<f32>) -> (f32, f32) {
+    let total = absorption.sum();
+    let b = (-total).exp();
+    match total {
+        0.0 => (b, 0.0),
+        _ => (b, absorption.x / total * (b - 1.0)),
+    }
+}
+
+impl PmlSlab<'_> {
+    /// Stretches the per axis second differences of one cell, given its
+    /// index in the slab and the grid, the absorption `σ·dt` at the cell
+    /// along each axis and the grid index of the cell whose `ψ` is on the
+    /// face below it along each axis, if there is one.
+    pub fn stretch(
+        &mut self,
+        local: usize,
+        idx: usize,
+        below: [Option<usize>; 3],
+        sigma: [Vector2<f32>; 3],
+        laplacian: Vector3<f32>,
+    ) -> Vector3<f32> {
+        Vector3::from_fn(|axis, _| {
+            let psi = &self.psi[axis];
+            let laplacian = laplacian[axis] + psi[idx] - below[axis].map_or(0.0, |x| psi[x]);
+
+            let zeta = &mut self.zeta[axis][local];
+            let (b, a) = coefficients(sigma[axis]);
+            *zeta = b * *zeta + a * laplacian;
+            laplacian + *zeta
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use compute::export::nalgebra::Vector3;
+
+    use super::Pml;
+    use crate::simulation::{boundary::BoundaryCondition, Config, Simulation};
+
+    /// The L2 norm of what's left after `steps` ticks of a pulse with no net
+    /// displacement in the middle of the grid, so nothing is left behind in
+    /// the free space case.
+    fn residual(boundary: BoundaryCondition, pml: Option<Pml>, steps: usize) -> f32 {
+        let size = Vector3::repeat(40);
+        let mut config = Config::new(size, 1.0, 0.1, 0.05).unwrap();
+        config.sources.clear();
+        config.boundaries = [boundary; 6];
+        config.pml = pml;
+
+        let mut simulation = Simulation::new(config).unwrap();
+        let center = size.map(|x| x as f32 - 1.0) / 2.0;
+        let pulse = (0..size.x * size.y * size.z)
+            .map(|i| {
+                let pos = Vector3::new(i / (size.y * size.z), i / size.z % size.y, i % size.z);
+                let distance = (pos.map(|x| x as f32) - center).norm_squared();
+                (distance / 9.0 - 3.0) * (-distance / 18.0).exp()
+            })
+            .collect::<Vec<_>>();
+        simulation.states = [(); 3].map(|_| pulse.clone());
+
+        for _ in 0..steps {
+            simulation.tick();
+        }
+        simulation.state().iter().map(|x| x * x).sum::<f32>().sqrt()
+    }
+
+    #[test]
+    fn pml_absorbs_better_than_mur() {
+        // By now the pulse has crossed the grid a couple of times, and what
+        // Mur reflects at oblique incidence is still bouncing around. The
+        // layer is thinner than the default to keep the grid small.
+        let pml = Pml {
+            thickness: 6,
+            ..Pml::default()
+        };
+        let mur = residual(BoundaryCondition::Absorbing, None, 200);
+        let pml = residual(BoundaryCondition::Dirichlet, Some(pml), 200);
+        assert!(
+            pml < mur / 100.0,
+            "PML residual {pml} vs Mur residual {mur}"
+        );
+    }
+}
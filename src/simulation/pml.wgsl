@@ -0,0 +1,41 @@
+// Helpers for the PML memory variables shared by `tick.wgsl` and `psi.wgsl`.
+// Need a `ctx` uniform with the grid `size` and boundary conditions, the
+// `sigma` profiles and the `PERIODIC` condition.
+
+// Absorption and frequency shift per tick at a cell, or at the face after it
+// along the axis, before they're scaled by the local wave speed. See
+// `Pml::profile`.
+fn absorption(axis: u32, coord: u32, face: bool) -> vec2f {
+    var offset = coord;
+    for (var i = 0u; i < axis; i++) {
+        offset += ctx.size[i];
+    }
+    return sigma[offset * 2u + u32(face)];
+}
+
+// The decay and gain of the recursive convolution, see `pml::coefficients`
+fn coefficients(absorption: vec2f) -> vec2f {
+    let total = absorption.x + absorption.y;
+    let b = exp(-total);
+    if total == 0.0 {
+        return vec2(b, 0.0);
+    }
+    return vec2(b, absorption.x / total * (b - 1.0));
+}
+
+// The cell sharing the face past `pos` along an axis, with `w` set if there
+// is one, see `pml::face_neighbor`
+fn face_neighbor(pos: vec3u, axis: u32, positive: bool) -> vec4u {
+    var other = pos;
+    if positive && pos[axis] + 1u < ctx.size[axis] {
+        other[axis] += 1u;
+    } else if !positive && pos[axis] > 0u {
+        other[axis] -= 1u;
+    } else if select(ctx.negative[axis], ctx.positive[axis], positive) == PERIODIC {
+        other[axis] = select(ctx.size[axis] - 1u, 0u, positive);
+    } else {
+        return vec4(pos, 0u);
+    }
+
+    return vec4(other, 1u);
+}
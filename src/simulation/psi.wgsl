@@ -0,0 +1,52 @@
+// The start of the uniform in `tick.wgsl`
+struct Uniform {
+    size: vec3u,
+    step: u32,
+    negative: vec3u,
+    courant: f32,
+    positive: vec3u
+}
+
+// See `Cell` in `tick.wgsl`
+struct Cell {
+    index: f32,
+    damping: f32,
+    obstacle: u32
+}
+
+// See `BoundaryCondition`
+const PERIODIC: u32 = 2u;
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+@group(0) @binding(1) var<storage, read> states: array<f32>;
+@group(0) @binding(2) var<storage, read> cells: array<Cell>;
+// ψ then ζ along each axis, see `tick.wgsl`
+@group(0) @binding(3) var<storage, read_write> pml: array<f32>;
+@group(0) @binding(4) var<storage, read> sigma: array<vec2f>;
+
+// Advances ψ on the face after each cell along every axis, before the tick
+// takes its divergence. See `PmlFields::update_psi`.
+@compute
+@workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) pos: vec3u) {
+    if any(pos >= ctx.size) {
+        return;
+    }
+
+    let count = ctx.size.x * ctx.size.y * ctx.size.z;
+    let curr = (ctx.step % 3u) * count;
+    let idx = index(pos);
+    for (var axis = 0u; axis < 3u; axis++) {
+        // Scaled by the local wave speed, see `Pml::sigma_max`
+        let s = absorption(axis, pos[axis], true) / cells[idx].index;
+        let next = face_neighbor(pos, axis, true);
+
+        var psi = 0.0;
+        if next.w != 0u && any(s != vec2(0.0)) {
+            let ba = coefficients(s);
+            let gradient = states[curr + index(next.xyz)] - states[curr + idx];
+            psi = ba.x * pml[axis * count + idx] + ba.y * gradient;
+        }
+        pml[axis * count + idx] = psi;
+    }
+}
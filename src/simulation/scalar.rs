@@ -0,0 +1,84 @@
+use std::fmt::Display;
+
+use compute::export::nalgebra::Vector3;
+
+use super::Simulation;
+
+/// Quantities the iso-surfaces can be coloured by, evaluated at each vertex.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    None,
+    /// Running average of the squared field.
+    Energy,
+    /// Magnitude of the spatial gradient of the field, per meter.
+    Gradient,
+    /// Distance to the nearest source, in meters, or zero without any.
+    SourceDistance,
+}
+
+impl Scalar {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::Energy,
+        Self::Gradient,
+        Self::SourceDistance,
+    ];
+
+    /// Evaluates the quantity at a position in cells.
+    pub fn evaluate(self, simulation: &Simulation, pos: Vector3<f32>) -> f32 {
+        let config = &simulation.config;
+        match self {
+            Self::None => 0.0,
+            Self::Energy => sample(&simulation.energy, config.size, pos),
+            Self::Gradient => {
+                let state = simulation.state();
+                let gradient = Vector3::from_fn(|axis, _| {
+                    let offset = Vector3::ith(axis, 0.5);
+                    sample(state, config.size, pos + offset)
+                        - sample(state, config.size, pos - offset)
+                });
+                gradient.norm() / config.dx
+            }
+            Self::SourceDistance => {
+                let distance = (config.sources.iter())
+                    .map(|source| (source.position - pos).norm())
+                    .reduce(f32::min);
+                distance.unwrap_or_default() * config.dx
+            }
+        }
+    }
+}
+
+/// Trilinearly interpolates the field at a position in cells, clamped to the
+/// grid.
+pub fn sample(field: &[f32], size: Vector3<usize>, pos: Vector3<f32>) -> f32 {
+    let max = size.map(|x| x as f32 - 1.0);
+    let pos = pos.zip_map(&max, |x, max| x.clamp(0.0, max));
+    let low = pos.zip_map(&max, |x, max| x.floor().min(max - 1.0));
+    let t = pos - low;
+    let low = low.map(|x| x as usize);
+
+    let mut value = 0.0;
+    for i in 0..8 {
+        let offset = Vector3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+        let weight = Vector3::from_fn(|axis, _| match offset[axis] {
+            0 => 1.0 - t[axis],
+            _ => t[axis],
+        });
+        let pos = low + offset;
+        value += field[pos.x * size.y * size.z + pos.y * size.z + pos.z] * weight.product();
+    }
+
+    value
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "None",
+            Self::Energy => "Energy",
+            Self::Gradient => "Gradient Magnitude",
+            Self::SourceDistance => "Source Distance",
+        })
+    }
+}
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use compute::export::nalgebra::Vector3;
+use itertools::Itertools;
+
+/// Simple solids used to paint per-cell fields, with all positions in cells.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sphere {
+        center: Vector3<f32>,
+        radius: f32,
+    },
+    Box {
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+    },
+    /// Everything between `min` and `max` along `axis`.
+    Slab {
+        axis: usize,
+        min: f32,
+        max: f32,
+    },
+    /// A cylinder of the given `length` running along `axis`.
+    Cylinder {
+        center: Vector3<f32>,
+        radius: f32,
+        axis: usize,
+        length: f32,
+    },
+    /// A plate normal to `axis` spanning the whole grid, with `count` slits
+    /// cut out of it. The slits are spaced `separation` apart (center to
+    /// center) along the next axis and run the full length of the one after.
+    Slits {
+        center: Vector3<f32>,
+        axis: usize,
+        thickness: f32,
+        width: f32,
+        separation: f32,
+        count: usize,
+    },
+}
+
+impl Shape {
+    /// One of each kind of shape, roughly centered in a grid of the given size.
+    pub fn defaults(size: Vector3<usize>) -> [Self; 5] {
+        [
+            Self::default_sphere(size),
+            Self::default_box(size),
+            Self::default_slab(size),
+            Self::default_cylinder(size),
+            Self::default_slits(size),
+        ]
+    }
+
+    /// The center of the grid along with the radius the default shapes share.
+    fn default_extent(size: Vector3<usize>) -> (Vector3<f32>, f32) {
+        let size = size.map(|x| x as f32);
+        (size / 2.0, size.min() / 8.0)
+    }
+
+    pub fn default_sphere(size: Vector3<usize>) -> Self {
+        let (center, radius) = Self::default_extent(size);
+        Shape::Sphere { center, radius }
+    }
+
+    pub fn default_box(size: Vector3<usize>) -> Self {
+        let (center, radius) = Self::default_extent(size);
+        Shape::Box {
+            min: center.add_scalar(-radius),
+            max: center.add_scalar(radius),
+        }
+    }
+
+    pub fn default_slab(size: Vector3<usize>) -> Self {
+        let (center, radius) = Self::default_extent(size);
+        Shape::Slab {
+            axis: 0,
+            min: center.x - radius / 2.0,
+            max: center.x + radius / 2.0,
+        }
+    }
+
+    pub fn default_cylinder(size: Vector3<usize>) -> Self {
+        let (center, radius) = Self::default_extent(size);
+        Shape::Cylinder {
+            center,
+            radius,
+            axis: 1,
+            length: size.y as f32,
+        }
+    }
+
+    pub fn default_slits(size: Vector3<usize>) -> Self {
+        let (center, radius) = Self::default_extent(size);
+        Shape::Slits {
+            center,
+            axis: 0,
+            thickness: 2.0,
+            width: 2.0,
+            separation: radius,
+            count: 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Sphere { .. } => "Sphere",
+            Shape::Box { .. } => "Box",
+            Shape::Slab { .. } => "Slab",
+            Shape::Cylinder { .. } => "Cylinder",
+            Shape::Slits { .. } => "Slits",
+        }
+    }
+
+    pub fn contains(&self, pos: Vector3<f32>) -> bool {
+        match *self {
+            Shape::Sphere { center, radius } => {
+                (pos - center).magnitude_squared() <= radius.powi(2)
+            }
+            Shape::Box { min, max } => (0..3).all(|i| (min[i]..=max[i]).contains(&pos[i])),
+            Shape::Slab { axis, min, max } => (min..=max).contains(&pos[axis]),
+            Shape::Cylinder {
+                center,
+                radius,
+                axis,
+                length,
+            } => {
+                let offset = pos - center;
+                let radial = offset.magnitude_squared() - offset[axis].powi(2);
+                offset[axis].abs() <= length / 2.0 && radial <= radius.powi(2)
+            }
+            Shape::Slits {
+                center,
+                axis,
+                thickness,
+                width,
+                separation,
+                count,
+            } => {
+                let offset = pos - center;
+                let across = offset[(axis + 1) % 3];
+                let first = -(count.saturating_sub(1) as f32) * separation / 2.0;
+                let in_slit = (0..count)
+                    .any(|i| (across - (first + i as f32 * separation)).abs() <= width / 2.0);
+                offset[axis].abs() <= thickness / 2.0 && !in_slit
+            }
+        }
+    }
+
+    /// Sets every cell of `field` whose center lies inside the shape to `value`.
+    pub fn fill<T: Copy>(&self, field: &mut [T], size: Vector3<usize>, value: T) {
+        for ((x, y), z) in (0..size.x)
+            .cartesian_product(0..size.y)
+            .cartesian_product(0..size.z)
+        {
+            if self.contains(Vector3::new(x, y, z).map(|x| x as f32)) {
+                field[x * size.y * size.z + y * size.z + z] = value;
+            }
+        }
+    }
+}
+
+/// Parses shapes written as `kind:params`, with the parameters in the order
+/// they are declared in [`Shape`]. For example `sphere:50,50,50,10`,
+/// `box:0,0,0,10,10,10`, `slab:0,40,60`, `cylinder:50,50,50,5,1,100` or
+/// `slits:30,50,50,0,2,2,10,2`.
+impl FromStr for Shape {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, params) = s
+            .split_once(':')
+            .with_context(|| format!("Expected `kind:params` for shape `{s}`"))?;
+        let params = params
+            .split(',')
+            .map(|x| x.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid parameters for shape `{s}`"))?;
+
+        Ok(match (kind, &params[..]) {
+            ("sphere", &[x, y, z, radius]) => Shape::Sphere {
+                center: Vector3::new(x, y, z),
+                radius,
+            },
+            ("box", &[x0, y0, z0, x1, y1, z1]) => Shape::Box {
+                min: Vector3::new(x0, y0, z0),
+                max: Vector3::new(x1, y1, z1),
+            },
+            ("slab", &[axis, min, max]) if is_axis(axis) => Shape::Slab {
+                axis: axis as usize,
+                min,
+                max,
+            },
+            ("cylinder", &[x, y, z, radius, axis, length]) if is_axis(axis) => Shape::Cylinder {
+                center: Vector3::new(x, y, z),
+                radius,
+                axis: axis as usize,
+                length,
+            },
+            ("slits", &[x, y, z, axis, thickness, width, separation, count])
+                if is_axis(axis) && count >= 0.0 && count.fract() == 0.0 =>
+            {
+                Shape::Slits {
+                    center: Vector3::new(x, y, z),
+                    axis: axis as usize,
+                    thickness,
+                    width,
+                    separation,
+                    count: count as usize,
+                }
+            }
+            _ => bail!("Invalid shape `{s}`"),
+        })
+    }
+}
+
+fn is_axis(axis: f32) -> bool {
+    [0.0, 1.0, 2.0].contains(&axis)
+}
@@ -0,0 +1,124 @@
+struct Uniform {
+    size: vec3u,
+    step: u32,
+    // Boundary condition of the negative and positive face along each axis
+    negative: vec3u,
+    courant: f32,
+    positive: vec3u,
+    damping: f32,
+    dt: f32,
+    pml: u32,
+    source_count: u32,
+    // See `MAX_SOURCES`
+    sources: array<Source, 32>
+}
+
+struct Source {
+    position: vec3f,
+    width: f32,
+    value: f32
+}
+
+struct Cell {
+    index: f32,
+    damping: f32,
+    obstacle: u32
+}
+
+// See `BoundaryCondition`
+const DIRICHLET: u32 = 0u;
+const PERIODIC: u32 = 2u;
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+// The three ring buffered states, back to back
+@group(0) @binding(1) var<storage, read_write> states: array<f32>;
+@group(0) @binding(2) var<storage, read_write> energy: array<f32>;
+@group(0) @binding(3) var<storage, read> cells: array<Cell>;
+// The PML memory variables ψ along each axis, then ζ along each axis
+@group(0) @binding(4) var<storage, read_write> pml: array<f32>;
+// Absorption profiles along x, y and z, back to back
+@group(0) @binding(5) var<storage, read> sigma: array<vec2f>;
+
+// See `Simulation::tick`
+@compute
+@workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) pos: vec3u) {
+    let size = ctx.size;
+    if any(pos >= size) {
+        return;
+    }
+
+    let count = size.x * size.y * size.z;
+    let prev = ((ctx.step + 2u) % 3u) * count;
+    let curr = (ctx.step % 3u) * count;
+    let next = ((ctx.step + 1u) % 3u) * count;
+    let idx = index(pos);
+    let cell = cells[idx];
+    if cell.obstacle != 0u {
+        states[next + idx] = 0.0;
+        energy[idx] = 0.0;
+        return;
+    }
+
+    let center = states[curr + idx];
+    var laplacian = vec3(0.0);
+    for (var axis = 0u; axis < 3u; axis++) {
+        let a = neighbor(curr, pos, axis, true);
+        let b = neighbor(curr, pos, axis, false);
+        laplacian[axis] = a + b - 2.0 * center;
+    }
+
+    // Outside of the layer ψ and ζ stay zero, so every cell can be stretched
+    // the same way, see `PmlSlab::stretch`
+    if ctx.pml != 0u {
+        for (var axis = 0u; axis < 3u; axis++) {
+            let psi = axis * count;
+            let below = face_neighbor(pos, axis, false);
+            var psi_below = 0.0;
+            if below.w != 0u {
+                psi_below = pml[psi + index(below.xyz)];
+            }
+            laplacian[axis] += pml[psi + idx] - psi_below;
+
+            // Scaled by the local wave speed, see `Pml::sigma_max`
+            let ba = coefficients(absorption(axis, pos[axis], false) / cell.index);
+            let zeta = (3u + axis) * count + idx;
+            pml[zeta] = ba.x * pml[zeta] + ba.y * laplacian[axis];
+            laplacian[axis] += pml[zeta];
+        }
+    }
+
+    let scale = ctx.courant * ctx.courant / (cell.index * cell.index);
+    let loss = (ctx.damping + cell.damping) * ctx.dt / 2.0;
+    var sources = 0.0;
+    for (var i = 0u; i < ctx.source_count; i++) {
+        let source = ctx.sources[i];
+        sources += exp(-length(source.position - vec3f(pos)) / source.width) * source.value;
+    }
+
+    let sum = (laplacian.x + laplacian.y + laplacian.z) * scale;
+    let u = (sum - (1.0 - loss) * states[prev + idx] + 2.0 * center) / (1.0 + loss) + sources;
+
+    let nd = f32(ctx.step) + 1.0;
+    energy[idx] = energy[idx] * (f32(ctx.step) / nd) + u * u / nd;
+    states[next + idx] = u;
+}
+
+// The current state of the cell one step along an axis, see `boundary::neighbor`
+fn neighbor(curr: u32, pos: vec3u, axis: u32, positive: bool) -> f32 {
+    var other = pos;
+    if positive && pos[axis] + 1u < ctx.size[axis] {
+        other[axis] += 1u;
+    } else if !positive && pos[axis] > 0u {
+        other[axis] -= 1u;
+    } else {
+        let condition = select(ctx.negative[axis], ctx.positive[axis], positive);
+        if condition == DIRICHLET {
+            return 0.0;
+        } else if condition == PERIODIC {
+            other[axis] = select(ctx.size[axis] - 1u, 0u, positive);
+        }
+    }
+
+    return states[curr + index(other)];
+}
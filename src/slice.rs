@@ -0,0 +1,125 @@
+use anyhow::Result;
+use compute::{
+    bindings::{IndexBuffer, StorageBuffer, UniformBuffer, VertexBuffer},
+    export::{
+        nalgebra::{Matrix4, Vector3},
+        wgpu::{BlendState, CompareFunction, RenderPass, ShaderStages},
+    },
+    gpu::Gpu,
+    pipeline::render::RenderPipeline,
+};
+use encase::ShaderType;
+
+use crate::{
+    camera::Camera,
+    colormap::{self, ColorMap},
+    shader::{self, COLOR_MAP, INDEX, SAMPLE},
+    vertex::{Vertex, VERTEX_BUFFER_LAYOUT},
+};
+
+/// Axis aligned cross sections of the field, drawn as quads spanning the
+/// grid. Each fragment samples the field buffer at its position and colours
+/// it with a colour map, centered on zero.
+pub struct Slices {
+    render: RenderPipeline,
+    uniform: UniformBuffer<SliceUniform>,
+    index: IndexBuffer,
+    vertex: VertexBuffer<Vertex>,
+    size: Vector3<usize>,
+
+    /// Position of the slice normal to each axis in cells, if it's shown.
+    pub planes: [Option<f32>; 3],
+    /// Field magnitude mapped to the ends of the colour map.
+    pub range: f32,
+    pub opacity: f32,
+    /// Best left diverging, as the field is signed.
+    pub color_map: ColorMap,
+}
+
+#[derive(ShaderType, Default)]
+struct SliceUniform {
+    view_projection: Matrix4<f32>,
+    size: Vector3<u32>,
+    range: f32,
+    opacity: f32,
+    color_map: [Vector3<f32>; colormap::STOPS],
+}
+
+impl Slices {
+    pub fn new(gpu: &Gpu, field: &StorageBuffer<Vec<f32>>, size: Vector3<usize>) -> Result<Self> {
+        let uniform = gpu.create_uniform(&SliceUniform::default())?;
+        let render = gpu
+            .render_pipeline(shader::compose(
+                "slice.wgsl",
+                &[include_str!("slice.wgsl"), SAMPLE, INDEX, COLOR_MAP],
+            ))
+            .vertex_layout(VERTEX_BUFFER_LAYOUT)
+            .depth_compare(CompareFunction::Always)
+            .depth_write(false)
+            .blend(BlendState::PREMULTIPLIED_ALPHA_BLENDING)
+            .bind(&uniform, ShaderStages::VERTEX_FRAGMENT)
+            .bind(field, ShaderStages::FRAGMENT)
+            .finish();
+
+        Ok(Self {
+            render,
+            uniform,
+            index: gpu.create_index_empty(6 * 3),
+            vertex: gpu.create_vertex_empty(4 * 3)?,
+            size,
+
+            planes: [None; 3],
+            range: 1.0,
+            opacity: 0.8,
+            color_map: ColorMap::Coolwarm,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.planes.iter().any(Option::is_some)
+    }
+
+    pub fn render(&mut self, render_pass: &mut RenderPass, camera: &Camera, aspect: f32) {
+        let (vertices, indices) = self.quads();
+        self.vertex.upload(&vertices).unwrap();
+        self.index.upload(&indices).unwrap();
+
+        self.uniform
+            .upload(&SliceUniform {
+                view_projection: camera.view_projection(aspect),
+                size: self.size.map(|x| x as u32),
+                range: self.range,
+                opacity: self.opacity,
+                color_map: self.color_map.stops(),
+            })
+            .unwrap();
+
+        let count = indices.len() as u32;
+        self.render
+            .draw(render_pass, &self.index, &self.vertex, 0..count);
+    }
+
+    /// A quad spanning the grid for every shown slice.
+    fn quads(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let max = self.size.map(|x| x as f32 - 1.0);
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+
+        for (axis, plane) in self.planes.iter().enumerate() {
+            let Some(plane) = plane else {
+                continue;
+            };
+
+            let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+            let start = vertices.len() as u32;
+            for (i, j) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let mut pos = Vector3::zeros();
+                pos[axis] = plane.clamp(0.0, max[axis]);
+                (pos[a], pos[b]) = (i * max[a], j * max[b]);
+                vertices.push(Vertex::new(pos.push(1.0), Vector3::ith(axis, 1.0)));
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|x| start + x));
+        }
+
+        (vertices, indices)
+    }
+}
@@ -0,0 +1,37 @@
+struct VertexInput {
+    @location(0) pos: vec4f,
+    @location(1) normal: vec3f,
+    @location(2) level: u32
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) world_position: vec3f
+};
+
+struct Uniform {
+    view_projection: mat4x4f,
+    size: vec3u,
+    range: f32,
+    opacity: f32,
+    // See `colormap::STOPS`
+    color_map: array<vec3f, 9>
+}
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+@group(0) @binding(1) var<storage, read> field: array<f32>;
+
+@vertex
+fn vert(in: VertexInput) -> VertexOutput {
+    return VertexOutput(
+        ctx.view_projection * in.pos,
+        in.pos.xyz
+    );
+}
+
+@fragment
+fn frag(in: VertexOutput) -> @location(0) vec4f {
+    let value = sample(in.world_position) / ctx.range;
+    let color = color_map(value * 0.5 + 0.5);
+    return vec4(color * ctx.opacity, ctx.opacity);
+}
@@ -0,0 +1,192 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use compute::{
+    bindings::{IndexBuffer, RenderTarget, UniformBuffer, VertexBuffer},
+    export::{
+        nalgebra::{Vector2, Vector3},
+        wgpu::{
+            include_wgsl, BlendComponent, BlendFactor, BlendOperation, BlendState, Color,
+            ColorTargetState, ColorWrites, CompareFunction, RenderPass, ShaderModuleDescriptor,
+            ShaderStages, TextureFormat,
+        },
+    },
+    gpu::Gpu,
+    pipeline::render::RenderPipeline,
+};
+
+use crate::{
+    app::Uniform,
+    mesh::Mesh,
+    shader,
+    vertex::{Vertex, VERTEX_BUFFER_LAYOUT},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Depth tested solid surfaces, lit by a light at the camera.
+    Opaque,
+    /// Weighted blended order-independent transparency, so nested surfaces
+    /// look the same from every angle.
+    Transparent,
+}
+
+/// Draws translucent surfaces without sorting them. Each frame they are
+/// accumulated into two offscreen targets, which are then composited over
+/// whatever was already drawn, see `accumulate.wgsl` and `composite.wgsl`.
+pub struct Transparency {
+    accumulate: RenderPipeline,
+    targets: Option<Targets>,
+    index: IndexBuffer,
+    vertex: VertexBuffer<Vertex>,
+}
+
+/// The offscreen targets, recreated along with the pipeline that samples
+/// them whenever the window is resized.
+struct Targets {
+    accum: RenderTarget,
+    revealage: RenderTarget,
+    depth: RenderTarget,
+    composite: RenderPipeline,
+}
+
+const ACCUM_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const REVEALAGE_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+impl Transparency {
+    pub fn new(gpu: &Gpu, uniform: &UniformBuffer<Uniform>) -> Result<Self> {
+        // Colors are summed and the revealage is multiplied by one minus
+        // each fragment's alpha.
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let multiplicative = BlendComponent {
+            src_factor: BlendFactor::Zero,
+            dst_factor: BlendFactor::OneMinusSrc,
+            operation: BlendOperation::Add,
+        };
+
+        let accumulate = gpu
+            .render_pipeline(surface_shader(include_str!("accumulate.wgsl")))
+            .vertex_layout(VERTEX_BUFFER_LAYOUT)
+            .depth_compare(CompareFunction::Always)
+            .depth_write(false)
+            .targets(&[
+                ColorTargetState {
+                    format: ACCUM_FORMAT,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                },
+                ColorTargetState {
+                    format: REVEALAGE_FORMAT,
+                    blend: Some(BlendState {
+                        color: multiplicative,
+                        alpha: multiplicative,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                },
+            ])
+            .bind(uniform, ShaderStages::VERTEX_FRAGMENT)
+            .finish();
+
+        // A quad covering the whole screen in clip space
+        let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| Vertex::new(Vector3::new(x, y, 0.0).push(1.0), Vector3::z()));
+        let mut index = gpu.create_index_empty(6);
+        let mut vertex = gpu.create_vertex_empty(vertices.len() as u64)?;
+        index.upload(&[0, 1, 2, 0, 2, 3])?;
+        vertex.upload(&vertices)?;
+
+        Ok(Self {
+            accumulate,
+            targets: None,
+            index,
+            vertex,
+        })
+    }
+
+    /// Accumulates every mesh, then composites them onto the render pass.
+    pub fn render(
+        &mut self,
+        gpu: &Gpu,
+        render_pass: &mut RenderPass,
+        size: Vector2<u32>,
+        meshes: &[&Mesh],
+    ) {
+        if size.min() == 0 {
+            return;
+        }
+
+        if (self.targets.as_ref()).is_none_or(|x| x.accum.size() != size) {
+            self.targets = Some(Targets::new(gpu, size));
+        }
+        let targets = self.targets.as_ref().unwrap();
+
+        gpu.render_pass(
+            &[
+                (&targets.accum, Color::TRANSPARENT),
+                (&targets.revealage, Color::WHITE),
+            ],
+            Some(&targets.depth),
+            |pass| {
+                for mesh in meshes {
+                    (self.accumulate).draw(pass, &mesh.index, &mesh.vertex, 0..mesh.count);
+                }
+            },
+        );
+
+        (targets.composite).draw(render_pass, &self.index, &self.vertex, 0..6);
+    }
+}
+
+impl Targets {
+    fn new(gpu: &Gpu, size: Vector2<u32>) -> Self {
+        let accum = gpu.create_render_target(size, ACCUM_FORMAT);
+        let revealage = gpu.create_render_target(size, REVEALAGE_FORMAT);
+        let depth = gpu.create_render_target(size, TextureFormat::Depth32Float);
+
+        let composite = gpu
+            .render_pipeline(include_wgsl!("composite.wgsl"))
+            .vertex_layout(VERTEX_BUFFER_LAYOUT)
+            .depth_compare(CompareFunction::Always)
+            .depth_write(false)
+            .blend(BlendState::ALPHA_BLENDING)
+            .bind(&accum, ShaderStages::FRAGMENT)
+            .bind(&revealage, ShaderStages::FRAGMENT)
+            .finish();
+
+        Self {
+            accum,
+            revealage,
+            depth,
+            composite,
+        }
+    }
+}
+
+/// The shader for iso and obstacle surfaces, `render.wgsl`, with the given
+/// fragment entry point appended.
+pub fn surface_shader(fragment: &str) -> ShaderModuleDescriptor<'static> {
+    shader::compose(
+        "render.wgsl",
+        &[include_str!("render.wgsl"), shader::COLOR_MAP, fragment],
+    )
+}
+
+impl RenderMode {
+    pub const ALL: [Self; 2] = [Self::Opaque, Self::Transparent];
+}
+
+impl Display for RenderMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Opaque => "Opaque",
+            Self::Transparent => "Transparent",
+        })
+    }
+}
@@ -1,10 +1,13 @@
 use compute::export::{
-    egui::{emath::Numeric, DragValue, Ui},
-    nalgebra::Vector3,
+    egui::{emath::Numeric, Align, Color32, DragValue, Layout, Rect, Sense, Ui, Vec2},
+    nalgebra::{Vector2, Vector3, Vector4},
 };
 use sci_dragger::SciDragValue;
 
+use crate::colormap::ColorMap;
+
 pub mod sci_dragger;
+pub mod shape;
 
 pub fn dragger<Num: Numeric>(
     ui: &mut Ui,
@@ -32,9 +35,45 @@ pub fn vec3_dragger<Num: Numeric>(
     });
 }
 
+/// Edits a colour with opacity, stored as unmultiplied RGBA.
+pub fn color_picker(ui: &mut Ui, color: &mut Vector4<f32>) {
+    let mut rgba = (*color).into();
+    ui.color_edit_button_rgba_unmultiplied(&mut rgba);
+    *color = rgba.into();
+}
+
 pub fn sci_dragger<Num: Numeric>(ui: &mut Ui, label: &str, value: &mut Num) {
     ui.horizontal(|ui| {
         SciDragValue::new(value).show(ui);
         ui.label(label);
     });
 }
+
+/// A horizontal bar showing the colour map, labeled with the values at
+/// either end of `range`.
+pub fn color_bar(ui: &mut Ui, map: ColorMap, range: Vector2<f32>) {
+    const WIDTH: f32 = 200.0;
+    const STRIPS: usize = 64;
+
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(WIDTH, 16.0), Sense::hover());
+    for i in 0..STRIPS {
+        let color = map.sample((i as f32 + 0.5) / STRIPS as f32);
+        let [r, g, b] = [color.x, color.y, color.z].map(|x| (x * 255.0) as u8);
+
+        let left = rect.left() + rect.width() * i as f32 / STRIPS as f32;
+        let strip = Rect::from_x_y_ranges(left..=left + WIDTH / STRIPS as f32, rect.y_range());
+        ui.painter()
+            .rect_filled(strip, 0.0, Color32::from_rgb(r, g, b));
+    }
+
+    ui.allocate_ui_with_layout(
+        Vec2::new(WIDTH, 0.0),
+        Layout::left_to_right(Align::Min),
+        |ui| {
+            ui.label(format!("{:.2e}", range.x));
+            ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                ui.label(format!("{:.2e}", range.y))
+            });
+        },
+    );
+}
@@ -0,0 +1,97 @@
+use compute::export::{
+    egui::{ComboBox, Ui},
+    nalgebra::Vector3,
+};
+
+use super::{dragger, vec3_dragger};
+use crate::simulation::shape::Shape;
+
+const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+/// Edits a shape in place, with `size` used to place a shape when switching
+/// to a different kind.
+pub fn shape_editor(ui: &mut Ui, shape: &mut Shape, size: Vector3<usize>) {
+    ComboBox::from_label("Shape")
+        .selected_text(shape.name())
+        .show_ui(ui, |ui| {
+            for default in Shape::defaults(size) {
+                let selected = default.name() == shape.name();
+                if ui.selectable_label(selected, default.name()).clicked() && !selected {
+                    *shape = default;
+                }
+            }
+        });
+
+    match shape {
+        Shape::Sphere { center, radius } => {
+            ui.horizontal(|ui| {
+                ui.label("Center");
+                vec3_dragger(ui, center, |x| x.speed(0.1));
+            });
+            dragger(ui, "Radius", radius, |x| x.speed(0.1).range(0.0..=f32::MAX));
+        }
+        Shape::Box { min, max } => {
+            ui.horizontal(|ui| {
+                ui.label("Min");
+                vec3_dragger(ui, min, |x| x.speed(0.1));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Max");
+                vec3_dragger(ui, max, |x| x.speed(0.1));
+            });
+        }
+        Shape::Slab { axis, min, max } => {
+            axis_selector(ui, axis);
+            dragger(ui, "Min", min, |x| x.speed(0.1));
+            dragger(ui, "Max", max, |x| x.speed(0.1));
+        }
+        Shape::Cylinder {
+            center,
+            radius,
+            axis,
+            length,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("Center");
+                vec3_dragger(ui, center, |x| x.speed(0.1));
+            });
+            axis_selector(ui, axis);
+            dragger(ui, "Radius", radius, |x| x.speed(0.1).range(0.0..=f32::MAX));
+            dragger(ui, "Length", length, |x| x.speed(0.1).range(0.0..=f32::MAX));
+        }
+        Shape::Slits {
+            center,
+            axis,
+            thickness,
+            width,
+            separation,
+            count,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("Center");
+                vec3_dragger(ui, center, |x| x.speed(0.1));
+            });
+            axis_selector(ui, axis);
+            dragger(ui, "Thickness", thickness, |x| {
+                x.speed(0.1).range(0.0..=f32::MAX)
+            });
+            dragger(ui, "Slit Width", width, |x| {
+                x.speed(0.1).range(0.0..=f32::MAX)
+            });
+            dragger(ui, "Separation", separation, |x| {
+                x.speed(0.1).range(0.0..=f32::MAX)
+            });
+            dragger(ui, "Slits", count, |x| x.range(0..=16));
+        }
+    }
+}
+
+fn axis_selector(ui: &mut Ui, axis: &mut usize) {
+    ComboBox::from_label("Axis")
+        .selected_text(AXES[*axis])
+        .show_ui(ui, |ui| {
+            for (i, name) in AXES.iter().enumerate() {
+                ui.selectable_value(axis, i, *name);
+            }
+        });
+}
@@ -5,7 +5,7 @@ use compute::export::{
 use encase::ShaderType;
 
 pub const VERTEX_BUFFER_LAYOUT: VertexBufferLayout = VertexBufferLayout {
-    array_stride: 32, // NOTE: WGSL alignment rules factor into this
+    array_stride: 48, // NOTE: WGSL alignment rules factor into this
     step_mode: VertexStepMode::Vertex,
     attributes: &[
         VertexAttribute {
@@ -18,6 +18,16 @@ pub const VERTEX_BUFFER_LAYOUT: VertexBufferLayout = VertexBufferLayout {
             offset: 4 * 4,
             shader_location: 1,
         },
+        VertexAttribute {
+            format: VertexFormat::Uint32,
+            offset: 4 * 4 + 3 * 4,
+            shader_location: 2,
+        },
+        VertexAttribute {
+            format: VertexFormat::Float32,
+            offset: 4 * 4 + 4 * 4,
+            shader_location: 3,
+        },
     ],
 };
 
@@ -25,10 +35,22 @@ pub const VERTEX_BUFFER_LAYOUT: VertexBufferLayout = VertexBufferLayout {
 pub struct Vertex {
     pub position: Vector4<f32>,
     pub normal: Vector3<f32>,
+    /// Index of the surface the vertex belongs to, used to pick its colour.
+    pub level: u32,
+    /// Quantity mapped through the colour map when colouring by a scalar.
+    pub scalar: f32,
 }
 
 impl Vertex {
+    /// Level of vertices on obstacle surfaces, which have their own colour.
+    pub const OBSTACLE: u32 = u32::MAX;
+
     pub fn new(position: Vector4<f32>, normal: Vector3<f32>) -> Self {
-        Self { position, normal }
+        Self {
+            position,
+            normal,
+            level: 0,
+            scalar: 0.0,
+        }
     }
 }
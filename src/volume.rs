@@ -0,0 +1,142 @@
+use anyhow::Result;
+use compute::{
+    bindings::{IndexBuffer, StorageBuffer, UniformBuffer, VertexBuffer},
+    export::{
+        nalgebra::{Matrix4, Vector3, Vector4},
+        wgpu::{BlendState, CompareFunction, RenderPass, ShaderStages},
+    },
+    gpu::Gpu,
+    pipeline::render::RenderPipeline,
+};
+use encase::ShaderType;
+
+use crate::{
+    camera::Camera,
+    shader::{self, INDEX, SAMPLE},
+    vertex::{Vertex, VERTEX_BUFFER_LAYOUT},
+};
+
+/// Renders the field directly by ray marching through it on the GPU. The
+/// field is read from a storage buffer with the same layout as on the CPU
+/// and trilinearly interpolated in the shader. Rays are cast from the back
+/// faces of the grid's bounding box, so the camera can also be inside it.
+///
+/// The buffer is the same one the slices and GPU mesher read. A 3D texture
+/// wouldn't save the interpolation, as `r32float` textures can't be
+/// filtered without an optional feature, and would need another copy of the
+/// field every time it changes.
+pub struct Volume {
+    render: RenderPipeline,
+    uniform: UniformBuffer<VolumeUniform>,
+    index: IndexBuffer,
+    vertex: VertexBuffer<Vertex>,
+    size: Vector3<usize>,
+
+    pub transfer: TransferFunction,
+    /// Distance between samples along each ray, in cells.
+    pub step_size: f32,
+}
+
+/// Maps field values to a colour and an opacity per cell of distance.
+#[derive(ShaderType, Clone, Copy)]
+pub struct TransferFunction {
+    pub positive: Vector4<f32>,
+    pub negative: Vector4<f32>,
+    /// Magnitude below which values are fully transparent.
+    pub cutoff: f32,
+    /// Magnitude at which values reach their full opacity.
+    pub saturation: f32,
+}
+
+#[derive(ShaderType, Default)]
+struct VolumeUniform {
+    view_projection: Matrix4<f32>,
+    camera_position: Vector3<f32>,
+    size: Vector3<u32>,
+    step_size: f32,
+    transfer: TransferFunction,
+}
+
+impl Volume {
+    pub fn new(gpu: &Gpu, field: &StorageBuffer<Vec<f32>>, size: Vector3<usize>) -> Result<Self> {
+        let uniform = gpu.create_uniform(&VolumeUniform::default())?;
+        let render = gpu
+            .render_pipeline(shader::compose(
+                "volume.wgsl",
+                &[include_str!("volume.wgsl"), SAMPLE, INDEX],
+            ))
+            .vertex_layout(VERTEX_BUFFER_LAYOUT)
+            .depth_compare(CompareFunction::Always)
+            .depth_write(false)
+            .blend(BlendState::PREMULTIPLIED_ALPHA_BLENDING)
+            .bind(&uniform, ShaderStages::VERTEX_FRAGMENT)
+            .bind(field, ShaderStages::FRAGMENT)
+            .finish();
+
+        let (vertices, indices) = bounding_box(size);
+        let mut index = gpu.create_index_empty(indices.len() as u64);
+        let mut vertex = gpu.create_vertex_empty(vertices.len() as u64)?;
+        index.upload(&indices)?;
+        vertex.upload(&vertices)?;
+
+        Ok(Self {
+            render,
+            uniform,
+            index,
+            vertex,
+            size,
+
+            transfer: TransferFunction::default(),
+            step_size: 0.5,
+        })
+    }
+
+    pub fn render(&mut self, render_pass: &mut RenderPass, camera: &Camera, aspect: f32) {
+        self.uniform
+            .upload(&VolumeUniform {
+                view_projection: camera.view_projection(aspect),
+                camera_position: camera.position,
+                size: self.size.map(|x| x as u32),
+                step_size: self.step_size,
+                transfer: self.transfer,
+            })
+            .unwrap();
+
+        self.render
+            .draw(render_pass, &self.index, &self.vertex, 0..36);
+    }
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        Self {
+            positive: Vector4::new(1.0, 0.5, 0.2, 0.2),
+            negative: Vector4::new(0.2, 0.5, 1.0, 0.2),
+            cutoff: 0.05,
+            saturation: 1.0,
+        }
+    }
+}
+
+/// The six faces of the box spanning the grid, with outward facing normals.
+fn bounding_box(size: Vector3<usize>) -> (Vec<Vertex>, Vec<u32>) {
+    let max = size.map(|x| x as f32 - 1.0);
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+
+    for axis in 0..3 {
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        for positive in [false, true] {
+            let start = vertices.len() as u32;
+            let normal = Vector3::ith(axis, if positive { 1.0 } else { -1.0 });
+            for (i, j) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let mut pos = Vector3::zeros();
+                pos[axis] = positive as u8 as f32 * max[axis];
+                (pos[a], pos[b]) = (i * max[a], j * max[b]);
+                vertices.push(Vertex::new(pos.push(1.0), normal));
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|x| start + x));
+        }
+    }
+
+    (vertices, indices)
+}
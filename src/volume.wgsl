@@ -0,0 +1,82 @@
+struct VertexInput {
+    @location(0) pos: vec4f,
+    @location(1) normal: vec3f,
+    @location(2) level: u32
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) normal: vec3f,
+    @location(1) world_position: vec3f
+};
+
+struct Uniform {
+    view_projection: mat4x4f,
+    camera_position: vec3f,
+    size: vec3u,
+    step_size: f32,
+    transfer: TransferFunction
+}
+
+struct TransferFunction {
+    positive: vec4f,
+    negative: vec4f,
+    cutoff: f32,
+    saturation: f32
+}
+
+@group(0) @binding(0) var<uniform> ctx: Uniform;
+@group(0) @binding(1) var<storage, read> field: array<f32>;
+
+@vertex
+fn vert(in: VertexInput) -> VertexOutput {
+    return VertexOutput(
+        ctx.view_projection * in.pos,
+        in.normal,
+        in.pos.xyz
+    );
+}
+
+@fragment
+fn frag(in: VertexOutput) -> @location(0) vec4f {
+    let ray = in.world_position - ctx.camera_position;
+    let dir = normalize(ray);
+
+    // Rays are cast from the back faces, so front faces are dropped to
+    // avoid marching through the volume twice.
+    if dot(in.normal, dir) < 0.0 {
+        discard;
+    }
+
+    // March from where the ray enters the grid, or the camera if it's
+    // already inside, to the back face.
+    let inv = 1.0 / select(dir, vec3(1e-6), abs(dir) < vec3(1e-6));
+    let t0 = -ctx.camera_position * inv;
+    let t1 = (vec3f(ctx.size - 1u) - ctx.camera_position) * inv;
+    let near = min(t0, t1);
+    let start = max(max(near.x, near.y), max(near.z, 0.0));
+    let steps = u32(ceil(max(length(ray) - start, 0.0) / ctx.step_size));
+
+    // Composite the samples front to back, stopping once nearly opaque
+    var color = vec3(0.0);
+    var alpha = 0.0;
+    for (var i = 0u; i < steps && alpha < 0.99; i++) {
+        let t = start + (f32(i) + 0.5) * ctx.step_size;
+        let point = transfer(sample(ctx.camera_position + dir * t));
+
+        // Opacity is given per cell, so correct it for the step size
+        let opacity = 1.0 - pow(1.0 - point.a, ctx.step_size);
+        color += (1.0 - alpha) * opacity * point.rgb;
+        alpha += (1.0 - alpha) * opacity;
+    }
+
+    return vec4(color, alpha);
+}
+
+fn transfer(value: f32) -> vec4f {
+    let tf = ctx.transfer;
+    let range = max(tf.saturation - tf.cutoff, 1e-6);
+    let strength = clamp((abs(value) - tf.cutoff) / range, 0.0, 1.0);
+    let color = select(tf.negative, tf.positive, value >= 0.0);
+    return vec4(color.rgb, color.a * strength);
+}